@@ -0,0 +1,348 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Benchmarks of BSTMap against std's BTreeMap and HashMap (cargo bench)
+//
+// BSTMap does no balancing yet (see README), so its shape depends on insertion order only -
+// random keys give a tree of logarithmic height, sorted keys give a linked list.
+// The sorted_insert group shows the difference, all the other groups use maps built from random keys.
+// When a balancing mode gets added, it only needs a BenchMap impl and a line in bench_all_maps
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hint::black_box,
+    ops::Bound,
+};
+
+use criterion::{
+    BatchSize, BenchmarkGroup, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main,
+    measurement::WallTime,
+};
+use rbst328::BSTMap;
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+type Group<'a> = BenchmarkGroup<'a, WallTime>;
+
+// Operations every benchmarked map has to provide
+trait BenchMap: Default + Clone + FromIterator<(u64, u64)> {
+    const NAME: &'static str;
+    // Only ordered maps take part in range scans
+    const ORDERED: bool = true;
+
+    fn insert(&mut self, key: u64, value: u64);
+    fn get(&self, key: &u64) -> Option<&u64>;
+    fn remove(&mut self, key: u64) -> Option<u64>;
+    fn sum_values(&self) -> u64;
+    fn range_len(&self, start: u64, end: u64) -> usize;
+}
+
+impl BenchMap for BSTMap<u64, u64> {
+    const NAME: &'static str = "BSTMap";
+
+    fn insert(&mut self, key: u64, value: u64) {
+        BSTMap::insert(self, key, value);
+    }
+
+    fn get(&self, key: &u64) -> Option<&u64> {
+        BSTMap::get(self, key)
+    }
+
+    fn remove(&mut self, key: u64) -> Option<u64> {
+        BSTMap::remove(self, key)
+    }
+
+    fn sum_values(&self) -> u64 {
+        self.values().sum()
+    }
+
+    fn range_len(&self, start: u64, end: u64) -> usize {
+        self.range((Bound::Included(start), Bound::Excluded(end)))
+            .count()
+    }
+}
+
+impl BenchMap for BTreeMap<u64, u64> {
+    const NAME: &'static str = "BTreeMap";
+
+    fn insert(&mut self, key: u64, value: u64) {
+        BTreeMap::insert(self, key, value);
+    }
+
+    fn get(&self, key: &u64) -> Option<&u64> {
+        BTreeMap::get(self, key)
+    }
+
+    fn remove(&mut self, key: u64) -> Option<u64> {
+        BTreeMap::remove(self, &key)
+    }
+
+    fn sum_values(&self) -> u64 {
+        self.values().sum()
+    }
+
+    fn range_len(&self, start: u64, end: u64) -> usize {
+        self.range(start..end).count()
+    }
+}
+
+impl BenchMap for HashMap<u64, u64> {
+    const NAME: &'static str = "HashMap";
+    const ORDERED: bool = false;
+
+    fn insert(&mut self, key: u64, value: u64) {
+        HashMap::insert(self, key, value);
+    }
+
+    fn get(&self, key: &u64) -> Option<&u64> {
+        HashMap::get(self, key)
+    }
+
+    fn remove(&mut self, key: u64) -> Option<u64> {
+        HashMap::remove(self, &key)
+    }
+
+    fn sum_values(&self) -> u64 {
+        self.values().sum()
+    }
+
+    fn range_len(&self, _start: u64, _end: u64) -> usize {
+        unreachable!("HashMap is not ordered")
+    }
+}
+
+// Even keys 0, 2, 4, ... in a fixed pseudo-random order, so that odd keys are guaranteed misses
+// and every run (and every map) sees exactly the same sequence
+fn shuffled_keys(len: usize) -> Vec<u64> {
+    let mut keys: Vec<u64> = (0..len as u64).map(|i| i * 2).collect();
+
+    // xorshift64, good enough for shuffling and keeps benchmarks free of extra dependencies
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    for i in (1..keys.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        keys.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+
+    keys
+}
+
+fn build<M: BenchMap>(keys: &[u64]) -> M {
+    keys.iter().map(|&key| (key, key)).collect()
+}
+
+fn insert_random<M: BenchMap>(group: &mut Group) {
+    for len in SIZES {
+        let keys = shuffled_keys(len);
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new(M::NAME, len), &keys, |b, keys| {
+            b.iter_with_large_drop(|| {
+                let mut map = M::default();
+                for &key in keys {
+                    map.insert(key, key);
+                }
+                map
+            })
+        });
+    }
+}
+
+fn insert_sorted<M: BenchMap>(group: &mut Group) {
+    for len in SIZES {
+        let keys: Vec<u64> = (0..len as u64).collect();
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new(M::NAME, len), &keys, |b, keys| {
+            b.iter_with_large_drop(|| {
+                let mut map = M::default();
+                for &key in keys {
+                    map.insert(key, key);
+                }
+                map
+            })
+        });
+    }
+}
+
+fn get_hit<M: BenchMap>(group: &mut Group) {
+    for len in SIZES {
+        let keys = shuffled_keys(len);
+        let map: M = build(&keys);
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new(M::NAME, len), &keys, |b, keys| {
+            b.iter(|| {
+                for key in keys {
+                    black_box(map.get(black_box(key)));
+                }
+            })
+        });
+    }
+}
+
+fn get_miss<M: BenchMap>(group: &mut Group) {
+    for len in SIZES {
+        let keys = shuffled_keys(len);
+        let map: M = build(&keys);
+        let misses: Vec<u64> = keys.iter().map(|key| key + 1).collect();
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new(M::NAME, len), &misses, |b, misses| {
+            b.iter(|| {
+                for key in misses {
+                    black_box(map.get(black_box(key)));
+                }
+            })
+        });
+    }
+}
+
+fn remove<M: BenchMap>(group: &mut Group) {
+    for len in SIZES {
+        let keys = shuffled_keys(len);
+        let map: M = build(&keys);
+        // removed in a different order than inserted, to hit nodes at all depths
+        let mut order = keys.clone();
+        order.reverse();
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new(M::NAME, len), &order, |b, order| {
+            b.iter_batched(
+                || map.clone(),
+                |mut map| {
+                    for &key in order {
+                        black_box(map.remove(key));
+                    }
+                    map
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+fn iterate<M: BenchMap>(group: &mut Group) {
+    for len in SIZES {
+        let map: M = build(&shuffled_keys(len));
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_function(BenchmarkId::new(M::NAME, len), |b| {
+            b.iter(|| black_box(map.sum_values()))
+        });
+    }
+}
+
+// Scans of 1/10 of the key space, starting at 10 evenly spread points
+fn range_scan<M: BenchMap>(group: &mut Group) {
+    if !M::ORDERED {
+        return;
+    }
+
+    for len in SIZES {
+        let map: M = build(&shuffled_keys(len));
+        let key_space = len as u64 * 2;
+        let width = key_space / 10;
+        let starts: Vec<u64> = (0..10).map(|i| i * key_space / 10).collect();
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new(M::NAME, len), &starts, |b, starts| {
+            b.iter(|| {
+                for &start in starts {
+                    black_box(map.range_len(start, start + width));
+                }
+            })
+        });
+    }
+}
+
+fn bulk_build<M: BenchMap>(group: &mut Group) {
+    for len in SIZES {
+        let keys = shuffled_keys(len);
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new(M::NAME, len), &keys, |b, keys| {
+            b.iter_with_large_drop(|| build::<M>(keys))
+        });
+    }
+}
+
+// Parallel collect sorts the entries and builds a balanced tree out of them
+#[cfg(feature = "rayon")]
+fn bulk_build_parallel(group: &mut Group) {
+    use rayon::prelude::*;
+
+    for len in SIZES {
+        let keys = shuffled_keys(len);
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(
+            BenchmarkId::new("BSTMap (par_iter)", len),
+            &keys,
+            |b, keys| {
+                b.iter_with_large_drop(|| {
+                    keys.par_iter()
+                        .map(|&key| (key, key))
+                        .collect::<BSTMap<u64, u64>>()
+                })
+            },
+        );
+    }
+}
+
+#[cfg(not(feature = "rayon"))]
+fn bulk_build_parallel(_group: &mut Group) {}
+
+// Runs a benchmark for every map, all in one group, so that reports compare them side by side
+macro_rules! bench_all_maps {
+    ($group:expr, $bench:ident) => {
+        $bench::<BSTMap<u64, u64>>($group);
+        $bench::<BTreeMap<u64, u64>>($group);
+        $bench::<HashMap<u64, u64>>($group);
+    };
+}
+
+fn maps(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_random");
+    bench_all_maps!(&mut group, insert_random);
+    group.finish();
+
+    let mut group = c.benchmark_group("insert_sorted");
+    // Quadratic for BSTMap, the default 100 samples would take minutes at the biggest size
+    group.sample_size(10);
+    bench_all_maps!(&mut group, insert_sorted);
+    group.finish();
+
+    let mut group = c.benchmark_group("get_hit");
+    bench_all_maps!(&mut group, get_hit);
+    group.finish();
+
+    let mut group = c.benchmark_group("get_miss");
+    bench_all_maps!(&mut group, get_miss);
+    group.finish();
+
+    let mut group = c.benchmark_group("remove");
+    bench_all_maps!(&mut group, remove);
+    group.finish();
+
+    let mut group = c.benchmark_group("iterate");
+    bench_all_maps!(&mut group, iterate);
+    group.finish();
+
+    let mut group = c.benchmark_group("range_scan");
+    bench_all_maps!(&mut group, range_scan);
+    group.finish();
+
+    let mut group = c.benchmark_group("bulk_build");
+    bench_all_maps!(&mut group, bulk_build);
+    bulk_build_parallel(&mut group);
+    group.finish();
+}
+
+criterion_group!(benches, maps);
+criterion_main!(benches);
@@ -35,7 +35,7 @@ fn main() {
         } else {
             let parts: Vec<&str> = cmd.trim().split(" ").collect();
 
-            if parts.len() < 1 {
+            if parts.is_empty() {
                 println!("Invalid command");
                 continue;
             }
@@ -103,18 +103,12 @@ fn main() {
             }
 
             if parts[0] == "keys" {
-                println!(
-                    "Ok, value = {:?}",
-                    bst.iter().map(|(k, _)| k).collect::<Vec<_>>()
-                );
+                println!("Ok, value = {:?}", bst.keys().collect::<Vec<_>>());
                 continue;
             }
 
             if parts[0] == "values" {
-                println!(
-                    "Ok, value = {:?}",
-                    bst.iter().map(|(_, v)| v).collect::<Vec<_>>()
-                );
+                println!("Ok, value = {:?}", bst.values().collect::<Vec<_>>());
                 continue;
             }
 
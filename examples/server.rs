@@ -0,0 +1,603 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Key-value server speaking the Redis protocol (RESP2), backed by BSTMap
+// Run with: cargo run --example server -- [address, default 127.0.0.1:6379]
+// and talk to it with: redis-cli -p 6379
+//
+// On top of the usual commands (GET, SET, DEL, EXISTS, DBSIZE, FLUSHDB, KEYS, PING, SCAN)
+// there are a few which make use of keys being sorted:
+// - KRANGE min max [LIMIT offset count] [WITHVALUES] - same syntax as ZRANGEBYLEX, e.g. [a (c - +
+// - KPREFIX prefix [LIMIT count]
+// - KRANK key - number of keys smaller than key, O(rank) since nodes do not store subtree sizes
+// - FIRSTKEY, LASTKEY
+// SCAN returns keys in order, its cursor is the (hex-encoded) key to continue from
+
+use rbst328::BSTMap;
+use std::{
+    env,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream},
+    ops::Bound,
+    sync::{Arc, RwLock},
+    thread,
+};
+
+type Db = Arc<RwLock<BSTMap<Vec<u8>, Vec<u8>>>>;
+
+// Limits on what a single client can make the server allocate
+// Inline limit is the same as in Redis, bulk limit is far below its 512 MB
+const MAX_INLINE_LEN: usize = 64 * 1024;
+const MAX_BULK_LEN: usize = 16 * 1024 * 1024;
+const MAX_ARRAY_LEN: usize = 1024 * 1024;
+
+enum Reply {
+    Status(&'static str),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        match self {
+            Reply::Status(status) => write!(out, "+{}\r\n", status),
+            Reply::Error(msg) => write!(out, "-{}\r\n", msg),
+            Reply::Integer(n) => write!(out, ":{}\r\n", n),
+            Reply::Bulk(None) => write!(out, "$-1\r\n"),
+            Reply::Bulk(Some(bytes)) => {
+                write!(out, "${}\r\n", bytes.len())?;
+                out.write_all(bytes)?;
+                write!(out, "\r\n")
+            }
+            Reply::Array(items) => {
+                write!(out, "*{}\r\n", items.len())?;
+                for item in items {
+                    item.write_to(out)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Protocol error: {}", msg),
+    )
+}
+
+// Reads a single line terminated with CRLF (or just LF, for inline commands typed by hand)
+// Returns None on clean end of stream
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+
+    // Room for CRLF on top of the limit - reaching it without a line feed means the line is too long
+    let limit = MAX_INLINE_LEN as u64 + 2;
+    if (&mut *reader).take(limit).read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+
+    if line.last() != Some(&b'\n') {
+        if line.len() as u64 == limit {
+            return Err(protocol_error("too big inline request"));
+        }
+        return Err(protocol_error("unexpected end of stream"));
+    }
+    line.pop();
+
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    Ok(Some(line))
+}
+
+fn parse_length(bytes: &[u8], max: usize) -> io::Result<usize> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|len| len.parse::<usize>().ok())
+        .filter(|len| *len <= max)
+        .ok_or_else(|| protocol_error("invalid length"))
+}
+
+// Reads a command, either as RESP array of bulk strings (what clients send)
+// or as an inline command separated with spaces (what people type into telnet)
+fn read_command<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let line = match read_line(reader)? {
+        None => return Ok(None),
+        Some(line) => line,
+    };
+
+    if line.first() != Some(&b'*') {
+        let args = line
+            .split(|byte| byte.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| arg.to_vec())
+            .collect();
+
+        return Ok(Some(args));
+    }
+
+    let arg_count = parse_length(&line[1..], MAX_ARRAY_LEN)?;
+    // Not preallocated up to the limit, arguments which never arrive should not cost anything
+    let mut args = Vec::with_capacity(arg_count.min(16));
+
+    for _ in 0..arg_count {
+        let header =
+            read_line(reader)?.ok_or_else(|| protocol_error("unexpected end of stream"))?;
+
+        if header.first() != Some(&b'$') {
+            return Err(protocol_error("expected bulk string"));
+        }
+
+        let len = parse_length(&header[1..], MAX_BULK_LEN)?;
+
+        // Grows as the data arrives, instead of allocating the whole declared length upfront
+        let mut arg = Vec::new();
+        (&mut *reader).take(len as u64 + 2).read_to_end(&mut arg)?;
+
+        if arg.len() < len + 2 {
+            return Err(protocol_error("unexpected end of stream"));
+        }
+        if !arg.ends_with(b"\r\n") {
+            return Err(protocol_error("bulk string not terminated with CRLF"));
+        }
+        arg.truncate(len);
+
+        args.push(arg);
+    }
+
+    Ok(Some(args))
+}
+
+// Redis-style glob matching: *, ?, [abc], [a-z], [^abc] and \ for escaping
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // position in pattern after the last *, and position in text it is matched against
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_class(pattern, p, text[t]),
+            Some(b'\\') if p + 1 < pattern.len() => (pattern[p + 1] == text[t]).then_some(p + 2),
+            Some(byte) => (*byte == text[t]).then_some(p + 1),
+            None => None,
+        };
+
+        match (step, backtrack) {
+            (Some(next_p), _) => {
+                p = next_p;
+                t += 1;
+            }
+            // Mismatch - let the last * swallow one more byte
+            (None, Some((star_p, star_t))) => {
+                p = star_p;
+                t = star_t + 1;
+                backtrack = Some((star_p, star_t + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|byte| *byte == b'*')
+}
+
+// Matches a byte against [...] class starting at pattern[start]
+// Returns position in pattern after the class if it matches
+fn match_class(pattern: &[u8], start: usize, byte: u8) -> Option<usize> {
+    let mut p = start + 1;
+    let negated = pattern.get(p) == Some(&b'^');
+    if negated {
+        p += 1;
+    }
+
+    let mut matched = false;
+    while p < pattern.len() && pattern[p] != b']' {
+        if pattern[p] == b'\\' && p + 1 < pattern.len() {
+            matched |= pattern[p + 1] == byte;
+            p += 2;
+        } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' && pattern[p + 2] != b']' {
+            let (low, high) = (
+                pattern[p].min(pattern[p + 2]),
+                pattern[p].max(pattern[p + 2]),
+            );
+            matched |= (low..=high).contains(&byte);
+            p += 3;
+        } else {
+            matched |= pattern[p] == byte;
+            p += 1;
+        }
+    }
+
+    // Unterminated class is treated as a literal [
+    if p >= pattern.len() {
+        return (byte == b'[').then_some(start + 1);
+    }
+
+    (matched != negated).then_some(p + 1)
+}
+
+// Parses a bound in ZRANGEBYLEX syntax: [key is inclusive, (key is exclusive, - and + mean no bound
+fn parse_lex_bound(arg: &[u8]) -> Option<Bound<Vec<u8>>> {
+    match arg.split_first() {
+        Some((b'-' | b'+', [])) => Some(Bound::Unbounded),
+        Some((b'[', key)) => Some(Bound::Included(key.to_vec())),
+        Some((b'(', key)) => Some(Bound::Excluded(key.to_vec())),
+        _ => None,
+    }
+}
+
+fn parse_count(arg: Option<Vec<u8>>) -> Option<usize> {
+    std::str::from_utf8(&arg?).ok()?.parse().ok()
+}
+
+fn parse_int(arg: Option<Vec<u8>>) -> Option<i64> {
+    std::str::from_utf8(&arg?).ok()?.parse().ok()
+}
+
+// SCAN cursors are hex-encoded key to resume from, so "0" (the cursor meaning start/end) never collides
+fn encode_cursor(key: &[u8]) -> Vec<u8> {
+    key.iter()
+        .flat_map(|byte| format!("{:02x}", byte).into_bytes())
+        .collect()
+}
+
+fn decode_cursor(cursor: &[u8]) -> Option<Vec<u8>> {
+    if !cursor.len().is_multiple_of(2) {
+        return None;
+    }
+
+    cursor
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn syntax_error() -> Reply {
+    Reply::Error("ERR syntax error".to_string())
+}
+
+fn wrong_arg_count(cmd: &str) -> Reply {
+    Reply::Error(format!(
+        "ERR wrong number of arguments for '{}' command",
+        cmd
+    ))
+}
+
+fn execute(db: &Db, args: Vec<Vec<u8>>) -> Reply {
+    let cmd = String::from_utf8_lossy(&args[0]).to_ascii_lowercase();
+    let mut args = args.into_iter().skip(1);
+    let arg_count = args.len();
+
+    match cmd.as_str() {
+        "ping" => match arg_count {
+            0 => Reply::Status("PONG"),
+            1 => Reply::Bulk(args.next()),
+            _ => wrong_arg_count(&cmd),
+        },
+
+        "get" => {
+            if arg_count != 1 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let key = args.next().unwrap();
+            Reply::Bulk(db.read().unwrap().get(&key).cloned())
+        }
+
+        "set" => {
+            if arg_count != 2 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let key = args.next().unwrap();
+            let value = args.next().unwrap();
+            db.write().unwrap().insert(key, value);
+            Reply::Status("OK")
+        }
+
+        "del" => {
+            if arg_count == 0 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let mut bst = db.write().unwrap();
+            let removed = args.filter(|key| bst.remove(key.clone()).is_some()).count();
+            Reply::Integer(removed as i64)
+        }
+
+        "exists" => {
+            if arg_count == 0 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let bst = db.read().unwrap();
+            let existing = args.filter(|key| bst.get(key).is_some()).count();
+            Reply::Integer(existing as i64)
+        }
+
+        "dbsize" => {
+            if arg_count != 0 {
+                return wrong_arg_count(&cmd);
+            }
+
+            Reply::Integer(db.read().unwrap().len() as i64)
+        }
+
+        "flushdb" => {
+            // FLUSHDB ASYNC/SYNC are accepted, the tree is cleared right away either way
+            if arg_count > 1 {
+                return wrong_arg_count(&cmd);
+            }
+
+            db.write().unwrap().clear();
+            Reply::Status("OK")
+        }
+
+        "keys" => {
+            if arg_count != 1 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let pattern = args.next().unwrap();
+            let keys = db
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|(key, _)| glob_match(&pattern, key))
+                .map(|(key, _)| Reply::Bulk(Some(key.clone())))
+                .collect();
+
+            Reply::Array(keys)
+        }
+
+        // KRANGE min max [LIMIT offset count] [WITHVALUES] - keys between min and max in order
+        // Same as in ZRANGEBYLEX, offset entries are skipped and negative count means all of them
+        "krange" => {
+            if arg_count < 2 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let bounds = (
+                parse_lex_bound(&args.next().unwrap()),
+                parse_lex_bound(&args.next().unwrap()),
+            );
+            let (Some(min), Some(max)) = bounds else {
+                return Reply::Error("ERR min or max not valid string range item".to_string());
+            };
+
+            let mut offset = 0;
+            let mut limit = usize::MAX;
+            let mut with_values = false;
+            while let Some(option) = args.next() {
+                if option.eq_ignore_ascii_case(b"limit") {
+                    let (Some(skip), Some(count)) =
+                        (parse_count(args.next()), parse_int(args.next()))
+                    else {
+                        return syntax_error();
+                    };
+                    offset = skip;
+                    limit = usize::try_from(count).unwrap_or(usize::MAX);
+                } else if option.eq_ignore_ascii_case(b"withvalues") {
+                    with_values = true;
+                } else {
+                    return syntax_error();
+                }
+            }
+
+            let bst = db.read().unwrap();
+            let mut items = Vec::new();
+            for (key, value) in bst.range((min, max)).skip(offset).take(limit) {
+                items.push(Reply::Bulk(Some(key.clone())));
+                if with_values {
+                    items.push(Reply::Bulk(Some(value.clone())));
+                }
+            }
+
+            Reply::Array(items)
+        }
+
+        // SCAN cursor [MATCH pattern] [COUNT count] - keys in order, count of them per call
+        "scan" => {
+            if arg_count < 1 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let cursor = args.next().unwrap();
+            let start = if cursor == b"0" {
+                Bound::Unbounded
+            } else {
+                match decode_cursor(&cursor) {
+                    Some(key) => Bound::Included(key),
+                    None => return Reply::Error("ERR invalid cursor".to_string()),
+                }
+            };
+
+            let mut pattern = None;
+            let mut count = 10;
+            while let Some(option) = args.next() {
+                if option.eq_ignore_ascii_case(b"match") {
+                    let Some(arg) = args.next() else {
+                        return syntax_error();
+                    };
+                    pattern = Some(arg);
+                } else if option.eq_ignore_ascii_case(b"count") {
+                    match parse_count(args.next()) {
+                        Some(arg) if arg > 0 => count = arg,
+                        _ => return syntax_error(),
+                    }
+                } else {
+                    return syntax_error();
+                }
+            }
+
+            // Same as in Redis, COUNT is the number of keys looked at, MATCH is applied afterwards
+            let bst = db.read().unwrap();
+            let mut scanned = bst.range((start, Bound::Unbounded));
+            let keys = scanned
+                .by_ref()
+                .take(count)
+                .filter(|(key, _)| {
+                    pattern
+                        .as_ref()
+                        .is_none_or(|pattern| glob_match(pattern, key))
+                })
+                .map(|(key, _)| Reply::Bulk(Some(key.clone())))
+                .collect();
+
+            let next_cursor = match scanned.next() {
+                Some((key, _)) => encode_cursor(key),
+                None => b"0".to_vec(),
+            };
+
+            Reply::Array(vec![Reply::Bulk(Some(next_cursor)), Reply::Array(keys)])
+        }
+
+        // KPREFIX prefix [LIMIT count] - keys starting with prefix, in order
+        "kprefix" => {
+            if arg_count != 1 && arg_count != 3 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let prefix = args.next().unwrap();
+            let limit = match args.next() {
+                None => usize::MAX,
+                Some(option) if option.eq_ignore_ascii_case(b"limit") => {
+                    match parse_count(args.next()) {
+                        Some(count) => count,
+                        None => return syntax_error(),
+                    }
+                }
+                Some(_) => return syntax_error(),
+            };
+
+            // All keys with the prefix are next to each other, starting at the prefix itself
+            let bst = db.read().unwrap();
+            let keys = bst
+                .range(prefix.clone()..)
+                .take_while(|(key, _)| key.starts_with(&prefix))
+                .take(limit)
+                .map(|(key, _)| Reply::Bulk(Some(key.clone())))
+                .collect();
+
+            Reply::Array(keys)
+        }
+
+        // KRANK key - number of keys smaller than key
+        // Walks all of those keys (nodes do not know the sizes of their subtrees),
+        // so it takes O(rank) time and holds the read lock for all of it
+        "krank" => {
+            if arg_count != 1 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let key = args.next().unwrap();
+            Reply::Integer(db.read().unwrap().range(..key).count() as i64)
+        }
+
+        "firstkey" | "lastkey" => {
+            if arg_count != 0 {
+                return wrong_arg_count(&cmd);
+            }
+
+            let bst = db.read().unwrap();
+            let entry = if cmd == "firstkey" {
+                bst.first_key_value()
+            } else {
+                bst.last_key_value()
+            };
+
+            Reply::Bulk(entry.map(|(key, _)| key.clone()))
+        }
+
+        // redis-cli asks for command docs on startup, it is fine with getting nothing back
+        "command" => Reply::Array(Vec::new()),
+
+        _ => Reply::Error(format!("ERR unknown command '{}'", cmd)),
+    }
+}
+
+fn handle_client(db: Db, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    loop {
+        let args = match read_command(&mut reader) {
+            Ok(None) => return Ok(()),
+            Ok(Some(args)) => args,
+            Err(err) => {
+                // Same as Redis - report the error and close the connection
+                Reply::Error(format!("ERR {}", err)).write_to(&mut writer)?;
+                return writer.flush();
+            }
+        };
+
+        if args.is_empty() {
+            continue;
+        }
+
+        if args[0].eq_ignore_ascii_case(b"quit") {
+            Reply::Status("OK").write_to(&mut writer)?;
+            return writer.flush();
+        }
+
+        execute(&db, args).write_to(&mut writer)?;
+
+        // Pipelined commands are answered in one go, flush once there is nothing more to read
+        if reader.buffer().is_empty() {
+            writer.flush()?;
+        }
+    }
+}
+
+fn main() {
+    let address = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:6379".to_string());
+
+    let listener = TcpListener::bind(&address).expect("Error while binding the address");
+    let db: Db = Arc::new(RwLock::new(BSTMap::new()));
+
+    println!("BSTMap server listening on {}", address);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                println!("Error while accepting connection: {}", err);
+                continue;
+            }
+        };
+
+        let db = Arc::clone(&db);
+        thread::spawn(move || {
+            let peer = stream.peer_addr();
+
+            if let Err(err) = handle_client(db, stream) {
+                println!("Error while serving {:?}: {}", peer, err);
+            }
+        });
+    }
+}
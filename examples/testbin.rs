@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use rbst328::BSTMap;
+use rbst328::{BSTMap, PrettyLayout};
 
 // TODO: remove this example file once unit tests feel complete enough?
 
@@ -34,7 +34,7 @@ fn main() {
     bst.insert(16, "utf8żółć".to_string());
 
     println!("PRINTING TREE");
-    bst.pretty_print();
+    print!("{}", bst.pretty(PrettyLayout::TopDown));
 
     let test1 = 15;
     println!("BST has {} {}", test1, bst.contains(test1));
@@ -46,13 +46,10 @@ fn main() {
     bst.remove(4);
 
     println!("PRINTING TREE AFTER REMOVAL");
-    bst.pretty_print();
+    print!("{}", bst.pretty(PrettyLayout::TopDown));
 
     {
-        let bst_ref = bst.get_mut(&16);
-
-        if bst_ref.is_some() {
-            let bst_ref = bst_ref.unwrap();
+        if let Some(bst_ref) = bst.get_mut(&16) {
             *bst_ref = "utf8żółćmodified".to_string();
         }
     }
@@ -69,7 +66,7 @@ fn main() {
     .into();
 
     println!("PRINTING TREE AFTER get_mut()");
-    bst.pretty_print();
+    print!("{}", bst.pretty(PrettyLayout::TopDown));
 
     for (key, value) in bst.iter() {
         println!("Mapping: {}: \"{}\"", key, value);
@@ -85,7 +82,7 @@ fn main() {
     );
 
     println!("PRINTING TREE AFTER extend()");
-    bst.pretty_print();
+    print!("{}", bst.pretty(PrettyLayout::TopDown));
 
     for (key, value) in bst.iter_breadthfirst() {
         println!("Mapping BFS: {}: \"{}\"", key, value);
@@ -96,7 +93,7 @@ fn main() {
     }
 
     println!("PRINTING TREE AFTER iter_mut()");
-    bst.pretty_print();
+    print!("{}", bst.pretty(PrettyLayout::TopDown));
 
     for (key, value) in bst {
         println!("Owned mapping: {}: \"{}\"", key, value);
@@ -0,0 +1,215 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Writes the seed corpus into corpus/<target>/, built from the cases in the crate's own tests
+// Run it once before fuzzing (cargo run --example generate_corpus), cargo fuzz picks the corpus up from there
+
+use std::{
+    fs, io,
+    ops::Bound,
+    path::{Path, PathBuf},
+};
+
+use rbst328::{BSTMap, DurableBSTMap, Encode, FsyncPolicy};
+use rbst328_fuzz::Op;
+
+// One file per seed
+type Seeds = Vec<Vec<u8>>;
+
+fn corpus_dir(target: &str) -> io::Result<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("corpus")
+        .join(target);
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn write_seeds(target: &str, seeds: &[Vec<u8>]) -> io::Result<()> {
+    let dir = corpus_dir(target)?;
+
+    for (i, seed) in seeds.iter().enumerate() {
+        fs::write(dir.join(format!("seed-{i}")), seed)?;
+    }
+
+    println!("{target}: {} seeds", seeds.len());
+    Ok(())
+}
+
+fn inserts(keys: &[u8]) -> Vec<Op> {
+    keys.iter()
+        .map(|&key| Op::Insert(key, key as u32 * 100))
+        .collect()
+}
+
+fn operation_seeds() -> Seeds {
+    // Trees from the removal tests in src/lib.rs
+    let one_child = [10, 5, 15, 2, 20];
+    let right_node_successor = [10, 5, 15, 2, 13, 20, 12, 14];
+    let successor_in_right_subtree = [10, 5, 15, 2, 13, 20, 12, 14, 19, 17, 21];
+
+    let sequences = [
+        [
+            inserts(&one_child),
+            vec![Op::Remove(5), Op::Remove(15), Op::Iter],
+        ]
+        .concat(),
+        [
+            inserts(&right_node_successor),
+            vec![Op::Remove(15), Op::Get(13), Op::Iter],
+        ]
+        .concat(),
+        [
+            inserts(&successor_in_right_subtree),
+            vec![Op::Remove(15), Op::Remove(10), Op::Get(17), Op::Iter],
+        ]
+        .concat(),
+        [
+            inserts(&successor_in_right_subtree),
+            vec![
+                Op::Range(Bound::Included(12), Bound::Excluded(20)),
+                Op::Range(Bound::Unbounded, Bound::Included(5)),
+                Op::PopFirst,
+                Op::PopLast,
+                Op::Clear,
+                Op::Insert(1, 1),
+                Op::Insert(1, 2),
+            ],
+        ]
+        .concat(),
+        // degenerate chain, removed from both ends
+        [
+            inserts(&[1, 2, 3, 4, 5, 6, 7, 8]),
+            vec![Op::PopFirst, Op::PopLast, Op::Remove(4)],
+        ]
+        .concat(),
+    ];
+
+    sequences.iter().map(|ops| Op::encode_all(ops)).collect()
+}
+
+fn encoded(value: &impl Encode) -> Vec<u8> {
+    let mut out = vec![];
+    value.encode(&mut out);
+    out
+}
+
+// The first byte picks the type, in the same order as in fuzz_targets/decode.rs
+fn decode_seeds() -> Seeds {
+    let values = [
+        encoded(&7u8),
+        encoded(&300u16),
+        encoded(&70_000u32),
+        encoded(&u64::MAX),
+        encoded(&(u128::MAX / 3)),
+        encoded(&-7i8),
+        encoded(&-300i16),
+        encoded(&-70_000i32),
+        encoded(&i64::MIN),
+        encoded(&(i128::MIN / 3)),
+        encoded(&true),
+        encoded(&"hello".to_string()),
+        encoded(&vec![0u8, 1, 255]),
+        encoded(&"zażółć".to_string()),
+    ];
+
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(kind, bytes)| [vec![kind as u8], bytes].concat())
+        .collect()
+}
+
+fn image_seeds() -> io::Result<Seeds> {
+    let trees: [&[u32]; 4] = [
+        &[],
+        &[10, 5, 15, 2, 20],
+        &[10, 5, 15, 2, 13, 20, 12, 14, 19, 17, 21],
+        &[1, 2, 3, 4, 5, 6, 7, 8],
+    ];
+
+    trees
+        .iter()
+        .map(|keys| {
+            let bst: BSTMap<_, _> = keys
+                .iter()
+                .map(|&key| (key, format!("value {key}")))
+                .collect();
+            let mut image = vec![];
+            bst.write_image(&mut image)?;
+            Ok(image)
+        })
+        .collect()
+}
+
+// Snapshots and log segments are taken from a real DurableBSTMap directory,
+// each one twice - with checksums left as they are and with fixing them turned on
+fn durable_seeds() -> io::Result<(Seeds, Seeds)> {
+    let dir = std::env::temp_dir().join(format!("rbst328_corpus_{}", std::process::id()));
+    let (mut snapshots, mut segments) = (vec![], vec![]);
+
+    let mut collect = |dir: &Path| -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let contents = fs::read(&path)?;
+
+            let seeds = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("snap") => &mut snapshots,
+                Some("log") => &mut segments,
+                _ => continue,
+            };
+            seeds.push([&[0], contents.as_slice()].concat());
+            seeds.push([&[1], contents.as_slice()].concat());
+        }
+        Ok(())
+    };
+
+    {
+        let mut bst = DurableBSTMap::<u32, String>::open(&dir, FsyncPolicy::Never)?;
+
+        for key in [10, 5, 15, 2, 20] {
+            bst.insert(key, format!("value {key}"))?;
+        }
+        bst.remove(5)?;
+
+        let mut transaction = bst.transaction();
+        transaction.insert(13, "in transaction".to_string());
+        transaction.remove(2);
+        transaction.commit()?;
+        bst.sync()?;
+        collect(&dir)?;
+
+        bst.compact()?;
+        bst.insert(1, "after compaction".to_string())?;
+        bst.clear()?;
+        bst.sync()?;
+        collect(&dir)?;
+    }
+
+    fs::remove_dir_all(&dir)?;
+    Ok((snapshots, segments))
+}
+
+fn main() -> io::Result<()> {
+    write_seeds("operations", &operation_seeds())?;
+    write_seeds("decode", &decode_seeds())?;
+    write_seeds("image", &image_seeds()?)?;
+
+    let (snapshots, segments) = durable_seeds()?;
+    write_seeds("snapshot", &snapshots)?;
+    write_seeds("wal", &segments)?;
+
+    Ok(())
+}
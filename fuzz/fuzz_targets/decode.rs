@@ -0,0 +1,64 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Feeds bytes to Decode and DecodeRef of the type picked by the first byte
+// Decoding has to either fail or give a value which encodes back into the very same bytes
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rbst328::{Decode, DecodeRef, Encode};
+
+fn roundtrip<T: Decode + Encode>(bytes: &[u8]) {
+    if let Ok(value) = T::decode(bytes) {
+        let mut encoded = vec![];
+        value.encode(&mut encoded);
+        assert_eq!(encoded, bytes);
+    }
+}
+
+// Borrowed values have no Encode, they have to simply point at all of the bytes
+fn roundtrip_ref<'a, T: DecodeRef<'a> + AsRef<[u8]>>(bytes: &'a [u8]) {
+    if let Ok(value) = T::decode_ref(bytes) {
+        assert_eq!(value.as_ref(), bytes);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((kind, bytes)) = data.split_first() else {
+        return;
+    };
+
+    match kind % 14 {
+        0 => roundtrip::<u8>(bytes),
+        1 => roundtrip::<u16>(bytes),
+        2 => roundtrip::<u32>(bytes),
+        3 => roundtrip::<u64>(bytes),
+        4 => roundtrip::<u128>(bytes),
+        5 => roundtrip::<i8>(bytes),
+        6 => roundtrip::<i16>(bytes),
+        7 => roundtrip::<i32>(bytes),
+        8 => roundtrip::<i64>(bytes),
+        9 => roundtrip::<i128>(bytes),
+        10 => roundtrip::<bool>(bytes),
+        11 => roundtrip::<String>(bytes),
+        12 => roundtrip::<Vec<u8>>(bytes),
+        _ => {
+            roundtrip_ref::<&str>(bytes);
+            roundtrip_ref::<&[u8]>(bytes);
+        }
+    }
+});
@@ -0,0 +1,48 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Opens arbitrary bytes as BSTMapImage and reads everything it claims to contain
+// Corrupted images have to give errors, not panics or endless iteration
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rbst328::BSTMapImage;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(image) = BSTMapImage::<u32, &str>::new(data) else {
+        return;
+    };
+
+    let mut keys = vec![];
+    for entry in image.iter() {
+        match entry {
+            Ok((key, _)) => keys.push(key),
+            Err(_) => break,
+        }
+    }
+    assert!(keys.len() <= image.len());
+
+    for key in &keys {
+        let _ = image.get(key);
+    }
+
+    if let (Some(first), Some(last)) = (keys.first(), keys.last())
+        && let Ok(range) = image.range(first..=last)
+    {
+        assert!(range.count() <= image.len());
+    }
+});
@@ -0,0 +1,63 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Applies a sequence of operations to both BSTMap and BTreeMap,
+// any difference in results or invalid tree (see BSTMap::validate) is a finding
+
+#![no_main]
+
+use std::{collections::BTreeMap, ops::RangeBounds};
+
+use libfuzzer_sys::fuzz_target;
+use rbst328::BSTMap;
+use rbst328_fuzz::Op;
+
+fuzz_target!(|data: &[u8]| {
+    let mut bst = BSTMap::new();
+    let mut model = BTreeMap::new();
+
+    for op in Op::parse_all(data) {
+        match op {
+            Op::Insert(key, value) => assert_eq!(bst.insert(key, value), model.insert(key, value)),
+            Op::Remove(key) => assert_eq!(bst.remove(key), model.remove(&key)),
+            Op::Get(key) => assert_eq!(bst.get(&key), model.get(&key)),
+            Op::Range(start, end) => {
+                // BTreeMap::range panics on ranges with start after end, filtering does not
+                let expected: Vec<_> = model
+                    .iter()
+                    .filter(|(key, _)| (start, end).contains(*key))
+                    .collect();
+                assert_eq!(bst.range((start, end)).collect::<Vec<_>>(), expected);
+            }
+            Op::PopFirst => assert_eq!(bst.pop_first(), model.pop_first()),
+            Op::PopLast => assert_eq!(bst.pop_last(), model.pop_last()),
+            Op::Iter => assert!(bst.iter().eq(model.iter())),
+            Op::Clear => {
+                bst.clear();
+                model.clear();
+            }
+        }
+
+        assert_eq!(bst.len(), model.len());
+        if let Err(error) = bst.validate() {
+            panic!("invalid tree after {op:?}: {error}");
+        }
+    }
+
+    // Whole-tree walks have to cope with whatever shape the operations left behind
+    assert_eq!(bst.stats().len, model.len());
+    assert!(bst.clone().into_iter().eq(model));
+});
@@ -0,0 +1,35 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Reads arbitrary bytes as a snapshot file, the low bit of the first byte decides
+// whether the checksum gets fixed (to get past it) or left as it is
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rbst328::fuzzing;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((flags, contents)) = data.split_first() else {
+        return;
+    };
+
+    if let Ok(bst) = fuzzing::decode_snapshot::<u32, String>(contents, flags & 1 == 1)
+        && let Err(error) = bst.validate()
+    {
+        panic!("invalid tree decoded from snapshot: {error}");
+    }
+});
@@ -0,0 +1,36 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Replays arbitrary bytes as a log segment, the low bit of the first byte decides
+// whether record checksums get fixed (to get past them) or left as they are
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rbst328::fuzzing;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((flags, contents)) = data.split_first() else {
+        return;
+    };
+
+    if let Ok((bst, valid_len)) = fuzzing::replay_log::<u32, String>(contents, flags & 1 == 1) {
+        assert!(valid_len <= contents.len());
+        if let Err(error) = bst.validate() {
+            panic!("invalid tree replayed from log: {error}");
+        }
+    }
+});
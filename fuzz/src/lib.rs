@@ -0,0 +1,133 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains the byte format of operation sequences, shared by the operations target
+// and the corpus generator (which has to write the same format the target reads)
+//
+// Every operation is an opcode byte followed by its arguments, keys being single bytes
+// (so that sequences often hit existing keys) and values being u32 LE
+
+use std::ops::Bound;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    Insert(u8, u32),
+    Remove(u8),
+    Get(u8),
+    Range(Bound<u8>, Bound<u8>),
+    PopFirst,
+    PopLast,
+    Iter,
+    Clear,
+}
+
+const OPCODES: u8 = 8;
+
+impl Op {
+    // Reads operations until the bytes run out, a cut short operation at the end is dropped
+    // Any byte is a valid opcode (modulo the number of operations), so every input means something
+    pub fn parse_all(mut bytes: &[u8]) -> Vec<Op> {
+        let mut ops = vec![];
+
+        while let Some((opcode, rest)) = bytes.split_first() {
+            bytes = rest;
+
+            let op = match opcode % OPCODES {
+                0 => match take(&mut bytes, 5) {
+                    Some([key, value @ ..]) => {
+                        // unwrap is safe, value is 4 bytes long
+                        Op::Insert(*key, u32::from_le_bytes(value.try_into().unwrap()))
+                    }
+                    _ => break,
+                },
+                1 => match take(&mut bytes, 1) {
+                    Some([key]) => Op::Remove(*key),
+                    _ => break,
+                },
+                2 => match take(&mut bytes, 1) {
+                    Some([key]) => Op::Get(*key),
+                    _ => break,
+                },
+                3 => match take(&mut bytes, 4) {
+                    Some([start_kind, start, end_kind, end]) => {
+                        Op::Range(bound(*start_kind, *start), bound(*end_kind, *end))
+                    }
+                    _ => break,
+                },
+                4 => Op::PopFirst,
+                5 => Op::PopLast,
+                6 => Op::Iter,
+                _ => Op::Clear,
+            };
+
+            ops.push(op);
+        }
+
+        ops
+    }
+
+    pub fn encode_all(ops: &[Op]) -> Vec<u8> {
+        let mut out = vec![];
+
+        for op in ops {
+            match *op {
+                Op::Insert(key, value) => {
+                    out.extend([0, key]);
+                    out.extend(value.to_le_bytes());
+                }
+                Op::Remove(key) => out.extend([1, key]),
+                Op::Get(key) => out.extend([2, key]),
+                Op::Range(start, end) => {
+                    out.push(3);
+                    out.extend(encode_bound(start));
+                    out.extend(encode_bound(end));
+                }
+                Op::PopFirst => out.push(4),
+                Op::PopLast => out.push(5),
+                Op::Iter => out.push(6),
+                Op::Clear => out.push(7),
+            }
+        }
+
+        out
+    }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if bytes.len() < n {
+        return None;
+    }
+
+    let (taken, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Some(taken)
+}
+
+fn bound(kind: u8, key: u8) -> Bound<u8> {
+    match kind % 3 {
+        0 => Bound::Included(key),
+        1 => Bound::Excluded(key),
+        _ => Bound::Unbounded,
+    }
+}
+
+fn encode_bound(bound: Bound<u8>) -> [u8; 2] {
+    match bound {
+        Bound::Included(key) => [0, key],
+        Bound::Excluded(key) => [1, key],
+        Bound::Unbounded => [2, 0],
+    }
+}
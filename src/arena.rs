@@ -0,0 +1,736 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains variant of BSTMap which keeps its nodes in a single Vec
+//
+// Nodes refer to each other with u32 indices into the Vec instead of Boxes. Slots of removed nodes
+// are linked into a free list and reused by later insertions, so the Vec only grows when there are
+// no free slots. There are no pointers between allocations, so dropping the map is a plain drop of the Vec
+
+use std::{
+    cmp::Ordering,
+    collections::VecDeque,
+    mem,
+    ops::{Bound, RangeBounds},
+};
+
+// Index of a missing node (same role as None in NodeRef)
+const NIL: u32 = u32::MAX;
+
+struct ArenaNode<K, V> {
+    left: u32,
+    right: u32,
+    key: K,
+    value: V,
+}
+
+enum Slot<K, V> {
+    Occupied(ArenaNode<K, V>),
+    // index of the next free slot, NIL if this is the last one
+    Free(u32),
+}
+
+pub struct ArenaBSTMap<K: Ord, V> {
+    slots: Vec<Slot<K, V>>,
+    head: u32,
+    free: u32,
+    length: usize,
+}
+
+impl<K: Ord, V> ArenaBSTMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    // Map which can hold capacity entries without reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            head: NIL,
+            free: NIL,
+            length: 0,
+        }
+    }
+
+    // Number of entries the map can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    // Makes sure that additional more entries can be inserted without reallocating
+    // Free slots left by removed entries count towards that
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.length + additional;
+        self.slots.reserve(needed.saturating_sub(self.slots.len()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    // Removes all entries, keeping the allocated memory
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.head = NIL;
+        self.free = NIL;
+        self.length = 0;
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        // Parent of the current node (NIL at the head) and which of its links leads to it
+        let mut parent = NIL;
+        let mut current = self.head;
+        let mut went_left = false;
+
+        while current != NIL {
+            let node = self.node_mut(current);
+
+            match node.key.cmp(&key) {
+                Ordering::Less => {
+                    parent = current;
+                    current = node.right;
+                    went_left = false;
+                }
+                Ordering::Greater => {
+                    parent = current;
+                    current = node.left;
+                    went_left = true;
+                }
+                Ordering::Equal => return Some(mem::replace(&mut node.value, value)),
+            }
+        }
+
+        let new_index = self.allocate(ArenaNode {
+            left: NIL,
+            right: NIL,
+            key,
+            value,
+        });
+
+        match parent {
+            NIL => self.head = new_index,
+            _ if went_left => self.node_mut(parent).left = new_index,
+            _ => self.node_mut(parent).right = new_index,
+        }
+
+        self.length += 1;
+        None
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.find(&key) != NIL
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        match self.find(key) {
+            NIL => None,
+            index => Some(&self.node(index).value),
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        match self.find(key) {
+            NIL => None,
+            index => Some(&mut self.node_mut(index).value),
+        }
+    }
+
+    // Entry with the smallest key
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let node = self.node(self.leftmost(self.head)?);
+        Some((&node.key, &node.value))
+    }
+
+    // Entry with the largest key
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut current = self.head;
+
+        if current == NIL {
+            return None;
+        }
+
+        while self.node(current).right != NIL {
+            current = self.node(current).right;
+        }
+
+        let node = self.node(current);
+        Some((&node.key, &node.value))
+    }
+
+    // Removes and returns the entry with the smallest key
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let index = self.leftmost(self.head)?;
+        let node = self.unlink(index);
+
+        Some((node.key, node.value))
+    }
+
+    // Removes and returns the entry with the largest key
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let mut current = self.head;
+
+        if current == NIL {
+            return None;
+        }
+
+        while self.node(current).right != NIL {
+            current = self.node(current).right;
+        }
+
+        let node = self.unlink(current);
+        Some((node.key, node.value))
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        match self.find(&key) {
+            NIL => None,
+            index => Some(self.unlink(index).value),
+        }
+    }
+
+    pub fn iter_inorder(&self) -> ArenaBSTMapInorderIter<'_, K, V> {
+        self.range(..)
+    }
+
+    pub fn iter_inorder_mut(&mut self) -> ArenaBSTMapInorderIterMut<'_, K, V> {
+        ArenaBSTMapInorderIterMut::new(self)
+    }
+
+    pub fn into_iter_inorder(self) -> ArenaBSTMapConsumingInorderIter<K, V> {
+        ArenaBSTMapConsumingInorderIter::new(self)
+    }
+
+    pub fn iter_breadthfirst(&self) -> ArenaBSTMapBreadthfirstIter<'_, K, V> {
+        let mut queue = VecDeque::with_capacity(self.len());
+
+        if self.head != NIL {
+            queue.push_back(self.head);
+        }
+
+        ArenaBSTMapBreadthfirstIter { bst: self, queue }
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> ArenaBSTMapInorderIter<'_, K, V> {
+        ArenaBSTMapInorderIter::new(self, range)
+    }
+
+    pub fn iter(&self) -> ArenaBSTMapInorderIter<'_, K, V> {
+        self.iter_inorder()
+    }
+
+    pub fn iter_mut(&mut self) -> ArenaBSTMapInorderIterMut<'_, K, V> {
+        self.iter_inorder_mut()
+    }
+
+    fn node(&self, index: u32) -> &ArenaNode<K, V> {
+        match &self.slots[index as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Free(_) => unreachable!("link to a free slot"),
+        }
+    }
+
+    fn node_mut(&mut self, index: u32) -> &mut ArenaNode<K, V> {
+        match &mut self.slots[index as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Free(_) => unreachable!("link to a free slot"),
+        }
+    }
+
+    fn find(&self, key: &K) -> u32 {
+        let mut current = self.head;
+
+        while current != NIL {
+            let node = self.node(current);
+
+            current = match node.key.cmp(key) {
+                Ordering::Less => node.right,
+                Ordering::Greater => node.left,
+                Ordering::Equal => return current,
+            }
+        }
+
+        NIL
+    }
+
+    fn leftmost(&self, mut current: u32) -> Option<u32> {
+        if current == NIL {
+            return None;
+        }
+
+        while self.node(current).left != NIL {
+            current = self.node(current).left;
+        }
+
+        Some(current)
+    }
+
+    // Puts the node into a free slot (or a new one), returns its index
+    fn allocate(&mut self, node: ArenaNode<K, V>) -> u32 {
+        if self.free == NIL {
+            assert!(self.slots.len() < NIL as usize, "ArenaBSTMap is full");
+
+            self.slots.push(Slot::Occupied(node));
+            return (self.slots.len() - 1) as u32;
+        }
+
+        let index = self.free;
+        self.free = match mem::replace(&mut self.slots[index as usize], Slot::Occupied(node)) {
+            Slot::Free(next) => next,
+            Slot::Occupied(_) => unreachable!("occupied slot on the free list"),
+        };
+
+        index
+    }
+
+    // Frees the slot, returning the node which was in it
+    fn release(&mut self, index: u32) -> ArenaNode<K, V> {
+        let slot = mem::replace(&mut self.slots[index as usize], Slot::Free(self.free));
+        self.free = index;
+
+        match slot {
+            Slot::Occupied(node) => node,
+            Slot::Free(_) => unreachable!("double free of a slot"),
+        }
+    }
+
+    // Replaces the link pointing at child (found by searching for its key) with replacement
+    fn relink(&mut self, child: u32, replacement: u32) {
+        let key = &self.node(child).key;
+        let mut parent = NIL;
+        let mut current = self.head;
+
+        while current != child {
+            parent = current;
+            let node = self.node(current);
+            current = match node.key.cmp(key) {
+                Ordering::Less => node.right,
+                _ => node.left,
+            };
+        }
+
+        if parent == NIL {
+            self.head = replacement;
+        } else if self.node(parent).left == child {
+            self.node_mut(parent).left = replacement;
+        } else {
+            self.node_mut(parent).right = replacement;
+        }
+    }
+
+    // Removes the node at index from the tree and frees its slot
+    fn unlink(&mut self, index: u32) -> ArenaNode<K, V> {
+        self.length -= 1;
+
+        let (left, right) = {
+            let node = self.node(index);
+            (node.left, node.right)
+        };
+
+        // At most one child - replace the node with it
+        if left == NIL || right == NIL {
+            self.relink(index, if left == NIL { right } else { left });
+            return self.release(index);
+        }
+
+        // Two children - detach the in-order successor (it has no left child)
+        // and move its key and value into this node
+        // unwrap is safe, right subtree is not empty
+        let successor = self.leftmost(right).unwrap();
+        let successor_right = self.node(successor).right;
+        self.relink(successor, successor_right);
+
+        let mut successor_node = self.release(successor);
+        let node = self.node_mut(index);
+        mem::swap(&mut node.key, &mut successor_node.key);
+        mem::swap(&mut node.value, &mut successor_node.value);
+
+        successor_node
+    }
+}
+
+impl<K: Ord, V> Default for ArenaBSTMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Implements In-Order iteration over entries with keys within a range (all of them for iter())
+pub struct ArenaBSTMapInorderIter<'a, K: Ord, V> {
+    bst: &'a ArenaBSTMap<K, V>,
+    // nodes whose key is yet to be returned, each one with its left subtree already explored
+    stack: Vec<u32>,
+    // last node within the range, iteration stops after it is returned
+    last: u32,
+}
+
+impl<'a, K: Ord, V> ArenaBSTMapInorderIter<'a, K, V> {
+    fn new<R: RangeBounds<K>>(bst: &'a ArenaBSTMap<K, V>, range: R) -> Self {
+        let mut stack = Vec::new();
+        let mut current = bst.head;
+
+        while current != NIL {
+            let node = bst.node(current);
+            let is_after_start = match range.start_bound() {
+                Bound::Included(start) => node.key >= *start,
+                Bound::Excluded(start) => node.key > *start,
+                Bound::Unbounded => true,
+            };
+
+            current = if is_after_start {
+                stack.push(current);
+                node.left
+            } else {
+                node.right
+            };
+        }
+
+        let mut last = NIL;
+        let mut current = bst.head;
+
+        while current != NIL {
+            let node = bst.node(current);
+            let is_before_end = match range.end_bound() {
+                Bound::Included(end) => node.key <= *end,
+                Bound::Excluded(end) => node.key < *end,
+                Bound::Unbounded => true,
+            };
+
+            current = if is_before_end {
+                last = current;
+                node.right
+            } else {
+                node.left
+            };
+        }
+
+        let is_empty = match stack.last() {
+            Some(&first) => last == NIL || bst.node(first).key > bst.node(last).key,
+            None => true,
+        };
+
+        if is_empty {
+            stack.clear();
+        }
+
+        Self { bst, stack, last }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for ArenaBSTMapInorderIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.stack.pop()?;
+        let node = self.bst.node(current);
+
+        if current == self.last {
+            self.stack.clear();
+        } else {
+            let mut next = node.right;
+            while next != NIL {
+                self.stack.push(next);
+                next = self.bst.node(next).left;
+            }
+        }
+
+        Some((&node.key, &node.value))
+    }
+}
+
+// Implements In-Order iteration with mutable access to values
+// Nodes are handed out in an order unrelated to their position in the Vec, so to stay within safe code
+// the order is computed up front and the slots are split into separate references
+pub struct ArenaBSTMapInorderIterMut<'a, K: Ord, V> {
+    order: std::vec::IntoIter<u32>,
+    entries: Vec<Option<(&'a K, &'a mut V)>>,
+}
+
+impl<'a, K: Ord, V> ArenaBSTMapInorderIterMut<'a, K, V> {
+    fn new(bst: &'a mut ArenaBSTMap<K, V>) -> Self {
+        let order = inorder_indices(bst);
+        let entries = bst
+            .slots
+            .iter_mut()
+            .map(|slot| match slot {
+                Slot::Occupied(node) => Some((&node.key, &mut node.value)),
+                Slot::Free(_) => None,
+            })
+            .collect();
+
+        Self {
+            order: order.into_iter(),
+            entries,
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for ArenaBSTMapInorderIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.order.next()?;
+        self.entries[index as usize].take()
+    }
+}
+
+// Consuming In-Order iterator, works the same way as ArenaBSTMapInorderIterMut
+pub struct ArenaBSTMapConsumingInorderIter<K, V> {
+    order: std::vec::IntoIter<u32>,
+    entries: Vec<Option<(K, V)>>,
+}
+
+impl<K: Ord, V> ArenaBSTMapConsumingInorderIter<K, V> {
+    fn new(bst: ArenaBSTMap<K, V>) -> Self {
+        let order = inorder_indices(&bst);
+        let entries = bst
+            .slots
+            .into_iter()
+            .map(|slot| match slot {
+                Slot::Occupied(node) => Some((node.key, node.value)),
+                Slot::Free(_) => None,
+            })
+            .collect();
+
+        Self {
+            order: order.into_iter(),
+            entries,
+        }
+    }
+}
+
+impl<K, V> Iterator for ArenaBSTMapConsumingInorderIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.order.next()?;
+        self.entries[index as usize].take()
+    }
+}
+
+// Implements breadth-first iterator over ArenaBSTMap
+pub struct ArenaBSTMapBreadthfirstIter<'a, K: Ord, V> {
+    bst: &'a ArenaBSTMap<K, V>,
+    queue: VecDeque<u32>,
+}
+
+impl<'a, K: Ord, V> Iterator for ArenaBSTMapBreadthfirstIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.bst.node(self.queue.pop_front()?);
+
+        if node.left != NIL {
+            self.queue.push_back(node.left);
+        }
+
+        if node.right != NIL {
+            self.queue.push_back(node.right);
+        }
+
+        Some((&node.key, &node.value))
+    }
+}
+
+// Indices of all nodes in key order
+fn inorder_indices<K: Ord, V>(bst: &ArenaBSTMap<K, V>) -> Vec<u32> {
+    let mut order = Vec::with_capacity(bst.len());
+    let mut stack = Vec::new();
+    let mut current = bst.head;
+
+    while current != NIL || !stack.is_empty() {
+        while current != NIL {
+            stack.push(current);
+            current = bst.node(current).left;
+        }
+
+        // unwrap is safe, either the loop above pushed something or the stack was not empty
+        let index = stack.pop().unwrap();
+        order.push(index);
+        current = bst.node(index).right;
+    }
+
+    order
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a ArenaBSTMap<K, V> {
+    type Item = (&'a K, &'a V);
+
+    type IntoIter = ArenaBSTMapInorderIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_inorder()
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a mut ArenaBSTMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    type IntoIter = ArenaBSTMapInorderIterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_inorder_mut()
+    }
+}
+
+impl<K: Ord, V> IntoIterator for ArenaBSTMap<K, V> {
+    type Item = (K, V);
+
+    type IntoIter = ArenaBSTMapConsumingInorderIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_iter_inorder()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for ArenaBSTMap<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut bst = Self::new();
+
+        for (k, v) in iter {
+            bst.insert(k, v);
+        }
+
+        bst
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for ArenaBSTMap<K, V> {
+    fn from(array: [(K, V); N]) -> Self {
+        let mut bst = Self::with_capacity(N);
+
+        for (k, v) in array {
+            bst.insert(k, v);
+        }
+
+        bst
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for ArenaBSTMap<K, V> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArenaBSTMap;
+
+    fn sample() -> ArenaBSTMap<u32, String> {
+        [
+            (10, "hello"),
+            (5, "hi"),
+            (15, "bye"),
+            (2, "leaf_node_child"),
+            (13, "left_child"),
+            (20, "right_child"),
+            (12, "left_child_subtree_l"),
+            (14, "left_child_subtree_r"),
+        ]
+        .map(|(k, v)| (k, v.to_string()))
+        .into()
+    }
+
+    fn keys(bst: &ArenaBSTMap<u32, String>) -> Vec<u32> {
+        bst.iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn behaves_like_bstmap() {
+        let mut bst = sample();
+
+        assert_eq!(bst.len(), 8);
+        assert_eq!(bst.get(&13).unwrap(), "left_child");
+        assert!(bst.contains(14));
+        assert_eq!(
+            bst.insert(13, "replaced".to_string()).unwrap(),
+            "left_child"
+        );
+        assert_eq!(keys(&bst), vec![2, 5, 10, 12, 13, 14, 15, 20]);
+        assert_eq!(
+            bst.iter_breadthfirst().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![10, 5, 15, 2, 13, 20, 12, 14]
+        );
+        assert_eq!(
+            bst.range(11..15).map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![12, 13, 14]
+        );
+    }
+
+    #[test]
+    fn removal_covers_all_cases() {
+        let mut bst = sample();
+
+        // leaf, node with one child, node with two children, root
+        assert_eq!(bst.remove(12).unwrap(), "left_child_subtree_l");
+        assert_eq!(bst.remove(5).unwrap(), "hi");
+        assert_eq!(bst.remove(15).unwrap(), "bye");
+        assert_eq!(bst.remove(10).unwrap(), "hello");
+        assert!(bst.remove(10).is_none());
+
+        assert_eq!(keys(&bst), vec![2, 13, 14, 20]);
+        assert_eq!(bst.pop_first().unwrap().0, 2);
+        assert_eq!(bst.pop_last().unwrap().0, 20);
+        assert_eq!(bst.len(), 2);
+    }
+
+    #[test]
+    fn freed_slots_are_reused() {
+        let mut bst = ArenaBSTMap::with_capacity(8);
+
+        for i in 0..8 {
+            bst.insert(i, i);
+        }
+
+        let capacity = bst.capacity();
+
+        for i in 0..4 {
+            bst.remove(i);
+        }
+        for i in 10..14 {
+            bst.insert(i, i);
+        }
+
+        assert_eq!(bst.slots.len(), 8);
+        assert_eq!(bst.capacity(), capacity);
+        assert_eq!(bst.len(), 8);
+
+        bst.reserve(100);
+        assert!(bst.capacity() >= 108);
+    }
+
+    #[test]
+    fn mutable_and_consuming_iterators_are_in_order() {
+        let mut bst = sample();
+        bst.remove(13);
+
+        for (k, v) in bst.iter_mut() {
+            *v = k.to_string();
+        }
+
+        assert_eq!(bst.get(&14).unwrap(), "14");
+        assert_eq!(
+            bst.into_iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            vec![2, 5, 10, 12, 14, 15, 20]
+        );
+    }
+}
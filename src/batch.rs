@@ -0,0 +1,332 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains batches of writes applied all at once and transactions built on top of them
+//
+// A batch only remembers the final state of each key it touches (new value or removal), plus whether
+// the whole map was cleared before those writes. That is equivalent to replaying the operations in order,
+// and it lets transactions look up their own writes in the batch
+
+use std::collections::VecDeque;
+
+use crate::{BSTMap, Comparator, OrdComparator, iter::BSTMapByrefInorderIter};
+
+// Keys are ordered (and deduplicated) by comparator C, same as in the map the batch is applied to
+pub struct WriteBatch<K, V, C = OrdComparator> {
+    pub(crate) cleared: bool,
+    // Some(value) for inserted keys, None for removed ones
+    pub(crate) writes: BSTMap<K, Option<V>, C>,
+}
+
+impl<K, V, C> WriteBatch<K, V, C> {
+    // Number of keys written to by the batch
+    pub fn len(&self) -> usize {
+        self.writes.length
+    }
+
+    // Whether applying the batch would do nothing
+    pub fn is_empty(&self) -> bool {
+        !self.cleared && self.writes.length == 0
+    }
+
+    // Writes in key order, None meaning removal
+    pub fn iter(&self) -> BSTMapByrefInorderIter<'_, K, Option<V>> {
+        BSTMapByrefInorderIter::new(&self.writes)
+    }
+}
+
+impl<K: Ord, V> WriteBatch<K, V> {
+    pub fn new() -> Self {
+        Self::with_comparator(OrdComparator)
+    }
+}
+
+impl<K, V, C: Comparator<K>> WriteBatch<K, V, C> {
+    pub fn with_comparator(comparator: C) -> Self {
+        Self {
+            cleared: false,
+            writes: BSTMap::with_comparator(comparator),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.writes.insert(key, Some(value));
+    }
+
+    pub fn remove(&mut self, key: K) {
+        self.writes.insert(key, None);
+    }
+
+    // Clears the map, along with all writes added to the batch so far
+    pub fn clear(&mut self) {
+        self.cleared = true;
+        self.writes.clear();
+    }
+
+    // Some(_) if the batch decides what key maps to, None if it is up to the map the batch is applied to
+    pub(crate) fn lookup(&self, key: &K) -> Option<Option<&V>> {
+        match self.writes.get(key) {
+            Some(write) => Some(write.as_ref()),
+            None if self.cleared => Some(None),
+            None => None,
+        }
+    }
+}
+
+impl<K, V, C: Comparator<K> + Default> Default for WriteBatch<K, V, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<K, V, C: Comparator<K>> BSTMap<K, V, C> {
+    pub fn apply_batch(&mut self, batch: WriteBatch<K, V, C>) {
+        if batch.cleared {
+            self.clear();
+        }
+
+        let writes = median_first(batch.writes.into_iter_inorder().collect());
+
+        for (key, write) in writes {
+            match write {
+                Some(value) => self.insert(key, value),
+                None => self.remove(key),
+            };
+        }
+    }
+}
+
+// Reorders sorted items so that each one comes before the halves on either side of it
+// Inserting them one by one in key order would build a single chain, in this order the tree is balanced
+pub(crate) fn median_first<T>(items: Vec<T>) -> Vec<T> {
+    let mut items: Vec<Option<T>> = items.into_iter().map(Some).collect();
+    let mut ordered = Vec::with_capacity(items.len());
+    let mut ranges = VecDeque::from([(0, items.len())]);
+
+    while let Some((start, end)) = ranges.pop_front() {
+        if start == end {
+            continue;
+        }
+
+        let middle = start + (end - start) / 2;
+        // unwrap is safe, ranges never overlap so every item is taken once
+        ordered.push(items[middle].take().unwrap());
+        ranges.push_back((start, middle));
+        ranges.push_back((middle + 1, end));
+    }
+
+    ordered
+}
+
+impl<K, V, C: Comparator<K> + Clone> BSTMap<K, V, C> {
+    // Starts a transaction, nothing is written to the map until it is committed
+    pub fn transaction(&mut self) -> Transaction<'_, K, V, C> {
+        let batch = WriteBatch::with_comparator(self.comparator.clone());
+        Transaction { bst: self, batch }
+    }
+}
+
+// Collects writes in a batch, reads see the map with the writes applied
+// Dropping the transaction without calling commit() rolls it back
+pub struct Transaction<'a, K, V, C: Comparator<K> = OrdComparator> {
+    bst: &'a mut BSTMap<K, V, C>,
+    batch: WriteBatch<K, V, C>,
+}
+
+impl<K, V, C: Comparator<K>> Transaction<'_, K, V, C> {
+    pub fn get(&self, key: &K) -> Option<&V> {
+        match self.batch.lookup(key) {
+            Some(write) => write,
+            None => self.bst.get(key),
+        }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.batch.insert(key, value);
+    }
+
+    pub fn remove(&mut self, key: K) {
+        self.batch.remove(key);
+    }
+
+    pub fn clear(&mut self) {
+        self.batch.clear();
+    }
+
+    pub fn commit(self) {
+        self.bst.apply_batch(self.batch);
+    }
+
+    // Same as dropping the transaction, but states the intent
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use crate::{BSTMap, Comparator};
+
+    use super::WriteBatch;
+
+    // Keys with the same last digit are equal, although Ord says otherwise
+    #[derive(Clone)]
+    struct LastDigit;
+
+    impl Comparator<u32> for LastDigit {
+        fn compare(&self, a: &u32, b: &u32) -> Ordering {
+            (a % 10).cmp(&(b % 10))
+        }
+
+        fn name(&self) -> &str {
+            "last_digit"
+        }
+    }
+
+    fn sample() -> BSTMap<u32, &'static str> {
+        let mut bst = BSTMap::new();
+
+        bst.insert(1, "one");
+        bst.insert(2, "two");
+        bst.insert(3, "three");
+
+        bst
+    }
+
+    #[test]
+    fn batch_applies_final_state_of_each_key() {
+        let mut bst = sample();
+        let mut batch = WriteBatch::new();
+
+        batch.insert(4, "four");
+        batch.remove(1);
+        batch.insert(1, "uno");
+        batch.remove(2);
+        batch.remove(5);
+
+        bst.apply_batch(batch);
+
+        assert_eq!(
+            bst.iter().collect::<Vec<_>>(),
+            vec![(&1, &"uno"), (&3, &"three"), (&4, &"four")]
+        );
+    }
+
+    #[test]
+    fn sorted_writes_build_a_balanced_tree() {
+        let mut bst = BSTMap::new();
+        let mut batch = WriteBatch::new();
+
+        for key in 0..1023u32 {
+            batch.insert(key, key);
+        }
+        bst.apply_batch(batch);
+
+        // 1023 nodes fit in 10 full levels, applying the writes in key order would give 1023 levels
+        let stats = bst.stats();
+        assert_eq!(stats.len, 1023);
+        assert_eq!(stats.height, 10);
+        assert!(bst.iter().map(|(k, _)| *k).eq(0..1023));
+    }
+
+    #[test]
+    fn batch_clear_drops_earlier_writes() {
+        let mut bst = sample();
+        let mut batch = WriteBatch::new();
+
+        batch.insert(4, "four");
+        batch.clear();
+        batch.insert(5, "five");
+
+        bst.apply_batch(batch);
+
+        assert_eq!(bst.iter().collect::<Vec<_>>(), vec![(&5, &"five")]);
+    }
+
+    #[test]
+    fn transaction_reads_its_own_writes() {
+        let mut bst = sample();
+        let mut transaction = bst.transaction();
+
+        transaction.insert(4, "four");
+        transaction.remove(1);
+
+        assert_eq!(transaction.get(&4), Some(&"four"));
+        assert_eq!(transaction.get(&1), None);
+        assert_eq!(transaction.get(&2), Some(&"two"));
+
+        transaction.clear();
+        transaction.insert(3, "tres");
+
+        assert_eq!(transaction.get(&2), None);
+        assert_eq!(transaction.get(&3), Some(&"tres"));
+
+        transaction.commit();
+
+        assert_eq!(bst.iter().collect::<Vec<_>>(), vec![(&3, &"tres")]);
+    }
+
+    #[test]
+    fn dropped_transaction_is_rolled_back() {
+        let mut bst = sample();
+
+        {
+            let mut transaction = bst.transaction();
+            transaction.insert(4, "four");
+            transaction.remove(1);
+        }
+
+        bst.transaction().rollback();
+
+        assert_eq!(bst.len(), 3);
+        assert!(bst.get(&4).is_none());
+        assert!(bst.get(&1).is_some());
+    }
+
+    #[test]
+    fn batch_and_transaction_use_comparator_of_the_map() {
+        let mut bst = BSTMap::with_comparator(LastDigit);
+        bst.insert(21, "twenty one");
+        bst.insert(5, "five");
+
+        let mut batch = WriteBatch::with_comparator(LastDigit);
+        batch.insert(3, "three");
+        batch.insert(13, "thirteen");
+        batch.remove(11);
+
+        // 3 and 13 are the same key to the comparator
+        assert_eq!(batch.len(), 2);
+        bst.apply_batch(batch);
+
+        assert_eq!(bst.len(), 2);
+        assert_eq!(bst.get(&3), Some(&"thirteen"));
+        assert_eq!(bst.get(&1), None);
+
+        let mut transaction = bst.transaction();
+        transaction.insert(15, "fifteen");
+        assert_eq!(transaction.get(&25), Some(&"fifteen"));
+        transaction.commit();
+
+        assert_eq!(
+            bst.values().copied().collect::<Vec<_>>(),
+            ["thirteen", "fifteen"]
+        );
+    }
+}
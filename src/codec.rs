@@ -0,0 +1,288 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains conversion of keys and values from/to bytes
+// Encoded values are not self-delimiting - whoever stores them (log, snapshot...)
+// is responsible for storing the length next to the bytes
+
+use std::io;
+
+// Types which can be turned into bytes
+pub trait Encode {
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+// Types which can be rebuilt from bytes produced by Encode
+// The slice always contains exactly the bytes of a single encoded value
+pub trait Decode: Sized {
+    fn decode(bytes: &[u8]) -> io::Result<Self>;
+}
+
+// Types which can be read straight out of a byte slice, borrowing from it where possible
+// Used to query data in place, e.g. in a memory mapped BSTMapImage
+pub trait DecodeRef<'a>: Sized {
+    fn decode_ref(bytes: &'a [u8]) -> io::Result<Self>;
+}
+
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+macro_rules! impl_codec_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl Encode for $t {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+
+            impl Decode for $t {
+                fn decode(bytes: &[u8]) -> io::Result<Self> {
+                    let array = bytes
+                        .try_into()
+                        .map_err(|_| invalid_data(concat!("invalid length for ", stringify!($t))))?;
+                    Ok(<$t>::from_le_bytes(array))
+                }
+            }
+
+            impl<'a> DecodeRef<'a> for $t {
+                fn decode_ref(bytes: &'a [u8]) -> io::Result<Self> {
+                    Self::decode(bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl Encode for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl Decode for bool {
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        match bytes {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(invalid_data("invalid encoding of bool")),
+        }
+    }
+}
+
+impl<'a> DecodeRef<'a> for bool {
+    fn decode_ref(bytes: &'a [u8]) -> io::Result<Self> {
+        Self::decode(bytes)
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Decode for String {
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid_data("string is not valid utf8"))
+    }
+}
+
+impl<'a> DecodeRef<'a> for &'a str {
+    fn decode_ref(bytes: &'a [u8]) -> io::Result<Self> {
+        std::str::from_utf8(bytes).map_err(|_| invalid_data("string is not valid utf8"))
+    }
+}
+
+impl Encode for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl Decode for Vec<u8> {
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl<'a> DecodeRef<'a> for &'a [u8] {
+    fn decode_ref(bytes: &'a [u8]) -> io::Result<Self> {
+        Ok(bytes)
+    }
+}
+
+// Helpers for length-prefixed framing used by the on-disk formats
+pub(crate) fn put_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+pub(crate) fn put_u64(out: &mut Vec<u8>, n: u64) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+// Encodes a value prefixed with its length as u32
+pub(crate) fn put_framed<T: Encode + ?Sized>(out: &mut Vec<u8>, value: &T) {
+    let len_pos = out.len();
+    put_u32(out, 0);
+    value.encode(out);
+
+    let value_len = (out.len() - len_pos - 4) as u32;
+    out[len_pos..len_pos + 4].copy_from_slice(&value_len.to_le_bytes());
+}
+
+// Cursor over a byte slice, every read fails with UnexpectedEof when data runs out
+pub(crate) struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    // Bytes which have not been read yet, without consuming them
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.pos.min(self.bytes.len())..]
+    }
+
+    pub(crate) fn read_bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> io::Result<u32> {
+        // unwrap is safe, read_bytes returns exactly 4 bytes
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> io::Result<u64> {
+        // unwrap is safe, read_bytes returns exactly 8 bytes
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    // Reads bytes written by put_framed()
+    pub(crate) fn read_framed(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.read_u32()? as usize;
+        self.read_bytes(len)
+    }
+}
+
+// CRC-32 (IEEE 802.3) used to detect torn or corrupted records
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ByteReader, Decode, Encode, crc32, put_framed};
+
+    #[test]
+    fn integers_roundtrip() {
+        let mut buf = Vec::new();
+        (-1234567i64).encode(&mut buf);
+
+        assert_eq!(buf.len(), 8);
+        assert_eq!(i64::decode(&buf).unwrap(), -1234567);
+    }
+
+    #[test]
+    fn decoding_integer_from_wrong_length_fails() {
+        assert!(u32::decode(&[1, 2, 3]).is_err());
+        assert!(u32::decode(&[1, 2, 3, 4, 5]).is_err());
+    }
+
+    #[test]
+    fn strings_roundtrip_and_reject_invalid_utf8() {
+        let mut buf = Vec::new();
+        "utf8 string ąąąąą".to_string().encode(&mut buf);
+
+        assert_eq!(String::decode(&buf).unwrap(), "utf8 string ąąąąą");
+        assert!(String::decode(&[0xff, 0xfe]).is_err());
+    }
+
+    #[test]
+    fn framed_values_are_read_back() {
+        let mut buf = Vec::new();
+        put_framed(&mut buf, &"hello".to_string());
+        put_framed(&mut buf, &42u16);
+
+        let mut reader = ByteReader::new(&buf);
+
+        assert_eq!(reader.read_framed().unwrap(), b"hello");
+        assert_eq!(u16::decode(reader.read_framed().unwrap()).unwrap(), 42);
+        assert!(reader.is_empty());
+        assert!(reader.read_framed().is_err());
+    }
+
+    #[test]
+    fn crc32_matches_reference_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,78 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains comparators - objects deciding the order of keys in a map, independent of Ord
+
+use std::{cmp::Ordering, ops::Bound};
+
+// Order of keys used by BSTMap, it has to be a total order (same requirements as Ord)
+pub trait Comparator<K: ?Sized> {
+    fn compare(&self, a: &K, b: &K) -> Ordering;
+
+    // Identifies the order in serialized forms of the map,
+    // so that they are not read back with a comparator ordering keys differently
+    fn name(&self) -> &str;
+}
+
+// Order given by Ord, used by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrdComparator;
+
+impl<K: Ord + ?Sized> Comparator<K> for OrdComparator {
+    fn compare(&self, a: &K, b: &K) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn name(&self) -> &str {
+        "ord"
+    }
+}
+
+// Reverse of the order given by Ord
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReverseComparator;
+
+impl<K: Ord + ?Sized> Comparator<K> for ReverseComparator {
+    fn compare(&self, a: &K, b: &K) -> Ordering {
+        b.cmp(a)
+    }
+
+    fn name(&self) -> &str {
+        "reverse"
+    }
+}
+
+// Whether key is not below the start bound of a range, according to comparator
+pub(crate) fn is_after_start<K, C: Comparator<K>>(
+    comparator: &C,
+    key: &K,
+    start: Bound<&K>,
+) -> bool {
+    match start {
+        Bound::Included(start) => comparator.compare(key, start).is_ge(),
+        Bound::Excluded(start) => comparator.compare(key, start).is_gt(),
+        Bound::Unbounded => true,
+    }
+}
+
+// Whether key is not above the end bound of a range, according to comparator
+pub(crate) fn is_before_end<K, C: Comparator<K>>(comparator: &C, key: &K, end: Bound<&K>) -> bool {
+    match end {
+        Bound::Included(end) => comparator.compare(key, end).is_le(),
+        Bound::Excluded(end) => comparator.compare(key, end).is_lt(),
+        Bound::Unbounded => true,
+    }
+}
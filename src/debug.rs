@@ -17,46 +17,438 @@
 // This file contains functionality used for debugging during development
 // This will most likely not be public API
 
-use std::fmt::Display;
-
-use super::BSTMap;
-
-impl<K: Display + Ord, V: Display> BSTMap<K, V> {
-    // TODO: Probably remove this/change to debug-only
-    // TODO: clean this code up and change it into a BFS function?
-    pub fn pretty_print(&self) {
-        let mut current_vector = vec![&self.head];
-
-        let mut space_count = 35;
-
-        while current_vector.iter().any(|node| node.is_some()) {
-            print!("{}", " ".repeat(space_count));
-            for node in &current_vector {
-                if node.is_some() {
-                    print!(
-                        "{:>3}:{:>3}   ",
-                        node.as_ref().unwrap().key,
-                        node.as_ref().unwrap().value
-                    );
-                } else {
-                    print!("X   ");
+use std::{
+    fmt::{self, Debug, Display},
+    io::{self, Write},
+};
+
+use super::{BSTMap, Node};
+
+// What to put in the DOT output besides keys and edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DotOptions {
+    // Show values next to keys
+    pub values: bool,
+    // Draw a point for every missing child, so that left and right edges can be told apart
+    pub null_children: bool,
+    // Annotate nodes with the height of their subtree (leaves have height 1)
+    pub height: bool,
+    // Annotate nodes with the number of entries in their subtree
+    pub size: bool,
+    // Fill nodes whose subtrees differ in height by more than 1 with this color (e.g. "tomato")
+    pub unbalanced_color: Option<&'static str>,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        Self {
+            values: true,
+            null_children: true,
+            height: false,
+            size: false,
+            unbalanced_color: None,
+        }
+    }
+}
+
+// How pretty() lays the tree out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrettyLayout {
+    // Root at the top, children below it - easy to read, but as wide as all labels put together
+    #[default]
+    TopDown,
+    // Rotated by 90 degrees, root on the left and right subtree above it - one line per entry,
+    // which suits deep or large trees
+    Sideways,
+}
+
+impl<K: Display, V: Display, C> BSTMap<K, V, C> {
+    // Renders the tree with "key:value" labels
+    pub fn pretty(&self, layout: PrettyLayout) -> String {
+        let mut out = String::new();
+        // writing to a String never fails
+        self.write_pretty(&mut out, layout).unwrap();
+        out
+    }
+
+    pub fn write_pretty(&self, out: &mut impl fmt::Write, layout: PrettyLayout) -> fmt::Result {
+        self.write_pretty_with(out, layout, |key, value| format!("{key}:{value}"))
+    }
+}
+
+impl<K: Debug, V: Debug, C> BSTMap<K, V, C> {
+    // Same as pretty(), for keys or values which do not implement Display
+    pub fn pretty_debug(&self, layout: PrettyLayout) -> String {
+        let mut out = String::new();
+        // writing to a String never fails
+        self.write_pretty_debug(&mut out, layout).unwrap();
+        out
+    }
+
+    pub fn write_pretty_debug(
+        &self,
+        out: &mut impl fmt::Write,
+        layout: PrettyLayout,
+    ) -> fmt::Result {
+        self.write_pretty_with(out, layout, |key, value| format!("{key:?}:{value:?}"))
+    }
+}
+
+impl<K, V, C> BSTMap<K, V, C> {
+    fn write_pretty_with(
+        &self,
+        out: &mut impl fmt::Write,
+        layout: PrettyLayout,
+        label: impl Fn(&K, &V) -> String,
+    ) -> fmt::Result {
+        let (nodes, children) = self.flatten();
+        let labels: Vec<String> = nodes
+            .iter()
+            .map(|node| label(&node.key, &node.value))
+            .collect();
+
+        let lines = match layout {
+            PrettyLayout::TopDown => top_down(&labels, &children),
+            PrettyLayout::Sideways => sideways(&labels, &children),
+        };
+
+        for line in lines {
+            writeln!(out, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+
+    // Nodes in breadth-first order along with indices of their left and right children,
+    // so children always come after their parent
+    // No recursion, degenerate trees can be as deep as they are long
+    #[allow(clippy::type_complexity)]
+    fn flatten(&self) -> (Vec<&Node<K, V>>, Vec<(Option<usize>, Option<usize>)>) {
+        let mut nodes: Vec<&Node<K, V>> = self.head.as_deref().into_iter().collect();
+        let mut children = Vec::with_capacity(self.length);
+
+        let mut i = 0;
+        while i < nodes.len() {
+            let node = nodes[i];
+            let [left, right] = [&node.left, &node.right].map(|child| {
+                child.as_deref().map(|child| {
+                    nodes.push(child);
+                    nodes.len() - 1
+                })
+            });
+            children.push((left, right));
+            i += 1;
+        }
+
+        (nodes, children)
+    }
+}
+
+// Indices of nodes in key order, given children of every node (root being the first one)
+fn inorder(children: &[(Option<usize>, Option<usize>)]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(children.len());
+    let mut stack = vec![];
+    let mut current = (!children.is_empty()).then_some(0);
+
+    loop {
+        while let Some(i) = current {
+            stack.push(i);
+            current = children[i].0;
+        }
+
+        let Some(i) = stack.pop() else {
+            return order;
+        };
+        order.push(i);
+        current = children[i].1;
+    }
+}
+
+// Every label gets its own columns, in key order, so that no two labels overlap and every left subtree
+// is to the left of its parent. Each level of the tree is a line of labels, with underscores leading
+// to the children, followed by a line of slashes pointing at the children
+//
+//  _2:b_
+// /     \
+// 1:a   3:c
+fn top_down(labels: &[String], children: &[(Option<usize>, Option<usize>)]) -> Vec<String> {
+    let widths: Vec<usize> = labels.iter().map(|label| label.chars().count()).collect();
+
+    let mut columns = vec![0; labels.len()];
+    let mut next_column = 0;
+    for i in inorder(children) {
+        columns[i] = next_column;
+        next_column += widths[i] + 1;
+    }
+    let center = |i: usize| columns[i] + widths[i].saturating_sub(1) / 2;
+
+    // Breadth-first order means levels come one after another
+    let mut depths = vec![0; labels.len()];
+    for (i, (left, right)) in children.iter().enumerate() {
+        for child in [left, right].into_iter().flatten() {
+            depths[*child] = depths[i] + 1;
+        }
+    }
+
+    let levels = depths.last().map_or(0, |depth| depth + 1);
+    let mut lines = vec![vec![' '; next_column]; 2 * levels];
+
+    for (i, label) in labels.iter().enumerate() {
+        let (left, right) = children[i];
+        let (label_line, edge_line) = lines[2 * depths[i]..].split_at_mut(1);
+        let label_line = &mut label_line[0];
+
+        for (column, c) in label.chars().enumerate() {
+            label_line[columns[i] + column] = c;
+        }
+
+        if let Some(left) = left {
+            label_line[center(left) + 1..columns[i]].fill('_');
+            edge_line[0][center(left)] = '/';
+        }
+
+        if let Some(right) = right {
+            label_line[columns[i] + widths[i]..center(right)].fill('_');
+            edge_line[0][center(right)] = '\\';
+        }
+    }
+
+    // The last line only ever has edges of the deepest level, which has no children
+    lines.pop();
+    lines
+        .into_iter()
+        .map(|line| line.into_iter().collect())
+        .collect()
+}
+
+// Every entry gets a line, right subtrees above their parent and left ones below,
+// with vertical bars connecting each child to its parent
+//
+//     /-- 3:c
+// 2:b
+//     \-- 1:a
+fn sideways(labels: &[String], children: &[(Option<usize>, Option<usize>)]) -> Vec<String> {
+    enum Step {
+        // Lay out the subtree of node (children before and after the node itself)
+        Subtree(usize),
+        // Write the line of node
+        Line(usize),
+    }
+
+    // What goes before the label of every node: ancestors' bars and the edge to the parent
+    let mut prefixes = vec![String::new(); labels.len()];
+    let mut lines = Vec::with_capacity(labels.len());
+    let mut stack: Vec<Step> = (!labels.is_empty())
+        .then_some(Step::Subtree(0))
+        .into_iter()
+        .collect();
+
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Line(i) => lines.push(format!("{}{}", prefixes[i], labels[i])),
+            Step::Subtree(i) => {
+                let (left, right) = children[i];
+                let base = &prefixes[i];
+                // bars of ancestors carry on, the edge leading to this node turns into a bar
+                // (if there is more to connect on that side) or spaces
+                let above = base.replace("/-- ", "    ").replace("\\-- ", "|   ");
+                let below = base.replace("/-- ", "|   ").replace("\\-- ", "    ");
+
+                if let Some(left) = left {
+                    prefixes[left] = format!("{below}\\-- ");
+                    stack.push(Step::Subtree(left));
+                }
+                stack.push(Step::Line(i));
+                if let Some(right) = right {
+                    prefixes[right] = format!("{above}/-- ");
+                    stack.push(Step::Subtree(right));
                 }
             }
-            println!();
-
-            let mut next_vector = vec![];
-            for node in current_vector {
-                if node.is_some() {
-                    next_vector.push(&(node.as_ref().unwrap().left));
-                    next_vector.push(&(node.as_ref().unwrap().right));
-                } else {
-                    next_vector.push(&None);
-                    next_vector.push(&None);
+        }
+    }
+
+    lines
+}
+
+impl<K: Display, V: Display, C> BSTMap<K, V, C> {
+    // Writes the tree as a Graphviz digraph, render it with e.g. `dot -Tsvg tree.dot -o tree.svg`
+    pub fn to_dot(&self, out: impl Write) -> io::Result<()> {
+        self.to_dot_with(out, &DotOptions::default())
+    }
+
+    pub fn to_dot_with(&self, mut out: impl Write, options: &DotOptions) -> io::Result<()> {
+        let (nodes, children) = self.flatten();
+
+        // Heights and sizes of subtrees, children are visited before parents when going backwards
+        let mut heights = vec![0usize; nodes.len()];
+        let mut sizes = vec![0usize; nodes.len()];
+        for (i, (left, right)) in children.iter().enumerate().rev() {
+            let height_of = |child: &Option<usize>| child.map_or(0, |child| heights[child]);
+            let size_of = |child: &Option<usize>| child.map_or(0, |child| sizes[child]);
+
+            heights[i] = 1 + height_of(left).max(height_of(right));
+            sizes[i] = 1 + size_of(left) + size_of(right);
+        }
+
+        writeln!(out, "digraph BSTMap {{")?;
+        writeln!(out, "    node [shape=box];")?;
+
+        for (i, node) in nodes.iter().enumerate() {
+            let mut label = escape(&node.key.to_string());
+            if options.values {
+                label += &format!(": {}", escape(&node.value.to_string()));
+            }
+            if options.height {
+                label += &format!("\\nheight={}", heights[i]);
+            }
+            if options.size {
+                label += &format!("\\nsize={}", sizes[i]);
+            }
+
+            write!(out, "    n{i} [label=\"{label}\"")?;
+
+            let (left, right) = children[i];
+            let height_of = |child: Option<usize>| child.map_or(0, |child| heights[child]);
+            if let Some(color) = options.unbalanced_color
+                && height_of(left).abs_diff(height_of(right)) > 1
+            {
+                write!(out, ", style=filled, fillcolor=\"{}\"", escape(color))?;
+            }
+            writeln!(out, "];")?;
+
+            for (side, child) in [("L", left), ("R", right)] {
+                match child {
+                    Some(child) => writeln!(out, "    n{i} -> n{child} [label=\"{side}\"];")?,
+                    None if options.null_children => {
+                        writeln!(out, "    null{i}{side} [shape=point];")?;
+                        writeln!(out, "    n{i} -> null{i}{side} [label=\"{side}\"];")?;
+                    }
+                    None => {}
                 }
             }
-            current_vector = next_vector;
+        }
 
-            space_count -= 3;
+        writeln!(out, "}}")
+    }
+}
+
+// Escapes text to be put inside a quoted DOT string
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' | '"' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
         }
     }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BSTMap;
+
+    use super::{DotOptions, PrettyLayout};
+
+    fn dot_of(bst: &BSTMap<u32, &str>, options: &DotOptions) -> String {
+        let mut out = Vec::new();
+        bst.to_dot_with(&mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn dot_has_nodes_edges_and_null_children() {
+        let bst = BSTMap::from([(2, "two"), (1, "one"), (3, "say \"three\"")]);
+
+        let dot = dot_of(&bst, &DotOptions::default());
+
+        assert!(dot.starts_with("digraph BSTMap {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("n0 [label=\"2: two\"];"));
+        assert!(dot.contains("n2 [label=\"3: say \\\"three\\\"\"];"));
+        assert!(dot.contains("n0 -> n1 [label=\"L\"];"));
+        assert!(dot.contains("n0 -> n2 [label=\"R\"];"));
+        assert_eq!(dot.matches("[shape=point]").count(), 4);
+    }
+
+    #[test]
+    fn dot_annotations() {
+        let bst = BSTMap::from([(1, "a"), (2, "b"), (3, "c")]);
+        let options = DotOptions {
+            values: false,
+            null_children: false,
+            height: true,
+            size: true,
+            unbalanced_color: Some("tomato"),
+        };
+
+        let dot = dot_of(&bst, &options);
+
+        assert!(
+            dot.contains(
+                "n0 [label=\"1\\nheight=3\\nsize=3\", style=filled, fillcolor=\"tomato\"];"
+            )
+        );
+        assert!(dot.contains("n1 [label=\"2\\nheight=2\\nsize=2\"];"));
+        assert!(dot.contains("n2 [label=\"3\\nheight=1\\nsize=1\"];"));
+        assert!(!dot.contains("point"));
+    }
+
+    #[test]
+    fn pretty_top_down_adapts_to_label_widths() {
+        let bst: BSTMap<_, _> = BSTMap::from([
+            (20, "twenty"),
+            (1, "one"),
+            (300, "three hundred"),
+            (5, "five"),
+        ]);
+
+        assert_eq!(
+            bst.pretty(PrettyLayout::TopDown),
+            [
+                "   __________20:twenty_________",
+                "  /                            \\",
+                "1:one___               300:three hundred",
+                "        \\",
+                "      5:five",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn pretty_sideways_connects_children_to_parents() {
+        let bst: BSTMap<_, _> =
+            BSTMap::from([(4, 'd'), (2, 'b'), (6, 'f'), (1, 'a'), (3, 'c'), (5, 'e')]);
+
+        assert_eq!(
+            bst.pretty(PrettyLayout::Sideways),
+            [
+                "/-- 6:f",
+                "|   \\-- 5:e",
+                "4:d",
+                "|   /-- 3:c",
+                "\\-- 2:b",
+                "    \\-- 1:a",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn pretty_debug_and_empty_tree() {
+        let bst: BSTMap<_, _> = BSTMap::from([(vec![1], Some("a"))]);
+
+        assert_eq!(bst.pretty_debug(PrettyLayout::TopDown), "[1]:Some(\"a\")\n");
+        assert_eq!(BSTMap::<u8, u8>::new().pretty(PrettyLayout::Sideways), "");
+        assert_eq!(BSTMap::<u8, u8>::new().pretty(PrettyLayout::TopDown), "");
+    }
 }
@@ -0,0 +1,824 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains BSTMap wrapper which persists all mutations in a write-ahead log
+//
+// The map lives in a directory containing:
+// - snapshot-N.snap - contents of the map at the moment log segment N was started
+// - wal-N.log - log segments, only the newest one is written to
+// - comparator - name of the comparator ordering the keys, the map can not be opened with a different one
+//
+// On open the newest valid snapshot is loaded and only the segments starting from it are replayed,
+// so the time it takes depends on the size of the map and the compaction threshold, not on the history
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    BSTMap, Comparator, OrdComparator, WriteBatch,
+    codec::{Decode, Encode, invalid_data},
+    iter::BSTMapByrefInorderIter,
+    snapshot::{read_snapshot, write_snapshot},
+    wal::{FsyncPolicy, WalOp, WalWriter, replay_sealed},
+};
+
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+const SNAPSHOT_SUFFIX: &str = ".snap";
+// Snapshot being written, see write_snapshot()
+const SNAPSHOT_TMP_SUFFIX: &str = ".tmp";
+const SEGMENT_PREFIX: &str = "wal-";
+const SEGMENT_SUFFIX: &str = ".log";
+const COMPARATOR_FILE: &str = "comparator";
+
+fn snapshot_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{}{:020}{}", SNAPSHOT_PREFIX, seq, SNAPSHOT_SUFFIX))
+}
+
+fn snapshot_tmp_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!(
+        "{}{:020}{}",
+        SNAPSHOT_PREFIX, seq, SNAPSHOT_TMP_SUFFIX
+    ))
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{}{:020}{}", SEGMENT_PREFIX, seq, SEGMENT_SUFFIX))
+}
+
+// Sorted sequence numbers of all files in dir named prefix + number + suffix
+fn list_sequence_numbers(dir: &Path, prefix: &str, suffix: &str) -> io::Result<Vec<u64>> {
+    let mut numbers = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+
+        let seq = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|name| name.strip_suffix(suffix))
+            .and_then(|seq| seq.parse::<u64>().ok());
+
+        if let Some(seq) = seq {
+            numbers.push(seq);
+        }
+    }
+
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+// Makes sure the map in dir was created with comparator of the given name, records it if dir is new
+fn check_comparator(dir: &Path, name: &str) -> io::Result<()> {
+    let path = dir.join(COMPARATOR_FILE);
+
+    match fs::read(&path) {
+        Ok(stored) if stored == name.as_bytes() => Ok(()),
+        Ok(_) => Err(invalid_data("map was created with a different comparator")),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            // Same as with snapshots - written under a temporary name and renamed once synced,
+            // an empty file left by a crash would make every later open fail
+            let tmp_path = path.with_extension("tmp");
+            let mut file = fs::File::create(&tmp_path)?;
+            file.write_all(name.as_bytes())?;
+            file.sync_all()?;
+            drop(file);
+
+            fs::rename(&tmp_path, &path)?;
+            sync_dir(dir)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// Makes renames and file creations in dir durable
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    // Directories can not be opened as files on Windows, renames are durable there anyways
+    #[cfg(unix)]
+    fs::File::open(dir)?.sync_all()?;
+
+    #[cfg(not(unix))]
+    let _ = dir;
+
+    Ok(())
+}
+
+// Every mutation is appended to the log before it is applied to the map,
+// so if the call returns Ok the change will be there after reopening
+// (as long as it was fsync'ed, according to the FsyncPolicy)
+pub struct DurableBSTMap<K: Encode + Decode, V: Encode + Decode, C: Comparator<K> = OrdComparator> {
+    bst: BSTMap<K, V, C>,
+    dir: PathBuf,
+    policy: FsyncPolicy,
+    wal: WalWriter,
+    segment: u64,
+    compaction_threshold: Option<u64>,
+    // Set when compaction fails half way and it is not known which segment is the active one
+    poisoned: bool,
+}
+
+impl<K: Ord + Encode + Decode, V: Encode + Decode> DurableBSTMap<K, V> {
+    // Opens the map stored in dir (creating it if needed), loading the newest snapshot
+    // and replaying log segments written after it
+    pub fn open<P: AsRef<Path>>(dir: P, policy: FsyncPolicy) -> io::Result<Self> {
+        Self::open_with_comparator(dir, policy, OrdComparator)
+    }
+}
+
+impl<K: Encode + Decode, V: Encode + Decode, C: Comparator<K> + Clone> DurableBSTMap<K, V, C> {
+    // Same as open(), but keys are ordered by comparator
+    // Opening an existing map with a comparator of a different name fails
+    pub fn open_with_comparator<P: AsRef<Path>>(
+        dir: P,
+        policy: FsyncPolicy,
+        comparator: C,
+    ) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        check_comparator(&dir, comparator.name())?;
+
+        let snapshots = list_sequence_numbers(&dir, SNAPSHOT_PREFIX, SNAPSHOT_SUFFIX)?;
+        let segments = list_sequence_numbers(&dir, SEGMENT_PREFIX, SEGMENT_SUFFIX)?;
+
+        // Leftovers of a crash in the middle of writing a snapshot, never renamed into place
+        for seq in list_sequence_numbers(&dir, SNAPSHOT_PREFIX, SNAPSHOT_TMP_SUFFIX)? {
+            fs::remove_file(snapshot_tmp_path(&dir, seq))?;
+        }
+
+        // Newest snapshot which can be read, if there are none start with empty map
+        // Error of the newest unreadable snapshot is kept, it explains why older ones had to be used
+        let mut snapshot_error = None;
+        let mut loaded = None;
+
+        for seq in snapshots.iter().rev() {
+            match read_snapshot(&snapshot_path(&dir, *seq), comparator.clone()) {
+                Ok(bst) => {
+                    loaded = Some((bst, *seq));
+                    break;
+                }
+                Err(err) => {
+                    snapshot_error.get_or_insert(err);
+                }
+            }
+        }
+
+        let (mut bst, base) = match loaded {
+            Some(loaded) => loaded,
+            // Snapshots are renamed into place only once complete, so an unreadable one is corruption
+            None => match snapshot_error.take() {
+                Some(err) => return Err(err),
+                None => (BSTMap::with_comparator(comparator.clone()), 0),
+            },
+        };
+
+        // Segments after the snapshot have to form an unbroken sequence starting at the snapshot,
+        // otherwise some mutations were lost (e.g. newer snapshot is corrupted and we fell back to older one)
+        let segments: Vec<u64> = segments.into_iter().filter(|seq| *seq >= base).collect();
+        let is_contiguous = segments
+            .iter()
+            .enumerate()
+            .all(|(i, seq)| *seq == base + i as u64);
+
+        if !is_contiguous {
+            return Err(snapshot_error
+                .unwrap_or_else(|| invalid_data("write-ahead log segments are missing")));
+        }
+
+        // All segments but the newest one are sealed, newest one may end with a torn record
+        let active = segments.last().copied().unwrap_or(base);
+        for seq in segments.iter().filter(|seq| **seq != active) {
+            replay_sealed(&segment_path(&dir, *seq), &mut bst)?;
+        }
+
+        let wal = WalWriter::open(&segment_path(&dir, active), policy, &mut bst)?;
+
+        let durable = Self {
+            bst,
+            dir,
+            policy,
+            wal,
+            segment: active,
+            compaction_threshold: None,
+            poisoned: false,
+        };
+
+        // Leftovers of compaction interrupted after writing the snapshot
+        durable.remove_files_before(base)?;
+
+        Ok(durable)
+    }
+
+    // Logs the whole batch as a single record, so recovery sees either all of it or none
+    pub fn apply_batch(&mut self, batch: WriteBatch<K, V, C>) -> io::Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        self.append(&WalOp::Batch(&batch))?;
+        self.bst.apply_batch(batch);
+
+        self.compact_if_needed()
+    }
+
+    // Starts a transaction, nothing is logged or written to the map until it is committed
+    pub fn transaction(&mut self) -> DurableTransaction<'_, K, V, C> {
+        let batch = WriteBatch::with_comparator(self.bst.comparator().clone());
+        DurableTransaction {
+            durable: self,
+            batch,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bst.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bst.is_empty()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> io::Result<Option<V>> {
+        self.append(&WalOp::Insert(&key, &value))?;
+        let old_value = self.bst.insert(key, value);
+
+        self.compact_if_needed()?;
+        Ok(old_value)
+    }
+
+    pub fn remove(&mut self, key: K) -> io::Result<Option<V>> {
+        // Do not bother logging removal of keys which are not there
+        if self.bst.get(&key).is_none() {
+            return Ok(None);
+        }
+
+        self.append(&WalOp::Remove(&key))?;
+        let old_value = self.bst.remove(key);
+
+        self.compact_if_needed()?;
+        Ok(old_value)
+    }
+
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.append(&WalOp::Clear)?;
+        self.bst.clear();
+
+        self.compact_if_needed()
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.bst.contains(key)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.bst.get(key)
+    }
+
+    pub fn iter(&self) -> BSTMapByrefInorderIter<'_, K, V> {
+        self.bst.iter()
+    }
+
+    // Read-only access to the underlying map, mutations have to go through the wrapper
+    pub fn as_map(&self) -> &BSTMap<K, V, C> {
+        &self.bst
+    }
+
+    // Forces fsync of the log regardless of the FsyncPolicy
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.wal.sync()
+    }
+
+    // Size in bytes of the log segment currently written to
+    pub fn log_len(&self) -> u64 {
+        self.wal.len()
+    }
+
+    // Compact automatically once the current log segment grows past given size in bytes
+    // None (the default) means compaction only happens when compact() is called
+    // If compaction fails, the mutation which triggered it returns the error,
+    // but the mutation itself is already logged and applied
+    pub fn set_compaction_threshold(&mut self, threshold: Option<u64>) {
+        self.compaction_threshold = threshold;
+    }
+
+    // Starts a new log segment, writes a snapshot of the current contents
+    // and removes snapshots and segments which are no longer needed
+    pub fn compact(&mut self) -> io::Result<()> {
+        self.check_poisoned()?;
+        self.wal.sync()?;
+
+        let next_segment = self.segment + 1;
+
+        // New segment has to be on disk before the snapshot which makes the old one redundant
+        // If creating it fails, the old segment may or may not be followed by it on open,
+        // so neither of them can be safely written to anymore
+        // New segment is empty, so nothing gets replayed into the scratch map
+        let mut scratch = BSTMap::<K, V, C>::with_comparator(self.bst.comparator().clone());
+        let wal = WalWriter::open(
+            &segment_path(&self.dir, next_segment),
+            self.policy,
+            &mut scratch,
+        )
+        .and_then(|wal| sync_dir(&self.dir).map(|()| wal))
+        .inspect_err(|_| self.poisoned = true)?;
+
+        // Old segment is synced and complete, so from now on it is sealed, even without the snapshot
+        self.wal = wal;
+        self.segment = next_segment;
+
+        // Once the snapshot is renamed into place, it is used on open even if we crash below
+        write_snapshot(&snapshot_path(&self.dir, next_segment), &self.bst)?;
+        sync_dir(&self.dir)?;
+
+        self.remove_files_before(next_segment)
+    }
+
+    // Every mutation goes through here, so a poisoned map does not log anything
+    fn append(&mut self, op: &WalOp<'_, K, V, C>) -> io::Result<()> {
+        self.check_poisoned()?;
+        self.wal.append(op)
+    }
+
+    fn check_poisoned(&self) -> io::Result<()> {
+        if self.poisoned {
+            return Err(io::Error::other(
+                "durable map is unusable after a failed compaction, it has to be reopened",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn compact_if_needed(&mut self) -> io::Result<()> {
+        match self.compaction_threshold {
+            Some(threshold) if self.wal.len() >= threshold => self.compact(),
+            _ => Ok(()),
+        }
+    }
+
+    fn remove_files_before(&self, seq: u64) -> io::Result<()> {
+        for old in list_sequence_numbers(&self.dir, SNAPSHOT_PREFIX, SNAPSHOT_SUFFIX)? {
+            if old < seq {
+                fs::remove_file(snapshot_path(&self.dir, old))?;
+            }
+        }
+
+        for old in list_sequence_numbers(&self.dir, SEGMENT_PREFIX, SEGMENT_SUFFIX)? {
+            if old < seq {
+                fs::remove_file(segment_path(&self.dir, old))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Same as Transaction, but commit logs the batch before applying it
+// Dropping the transaction without calling commit() rolls it back
+pub struct DurableTransaction<
+    'a,
+    K: Encode + Decode,
+    V: Encode + Decode,
+    C: Comparator<K> + Clone = OrdComparator,
+> {
+    durable: &'a mut DurableBSTMap<K, V, C>,
+    batch: WriteBatch<K, V, C>,
+}
+
+impl<K: Encode + Decode, V: Encode + Decode, C: Comparator<K> + Clone>
+    DurableTransaction<'_, K, V, C>
+{
+    pub fn get(&self, key: &K) -> Option<&V> {
+        match self.batch.lookup(key) {
+            Some(write) => write,
+            None => self.durable.get(key),
+        }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.batch.insert(key, value);
+    }
+
+    pub fn remove(&mut self, key: K) {
+        self.batch.remove(key);
+    }
+
+    pub fn clear(&mut self) {
+        self.batch.clear();
+    }
+
+    pub fn commit(self) -> io::Result<()> {
+        self.durable.apply_batch(self.batch)
+    }
+
+    // Same as dropping the transaction, but states the intent
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        fs,
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::{DurableBSTMap, segment_path, snapshot_path, snapshot_tmp_path};
+    use crate::{ReverseComparator, wal::FsyncPolicy};
+
+    // Unique path in the temp directory, so that tests can run in parallel
+    pub(crate) fn temp_path(name: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let unique = COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!(
+            "rbst328_{}_{}_{}",
+            name,
+            std::process::id(),
+            unique
+        ))
+    }
+
+    #[test]
+    fn reopening_replays_all_mutations() {
+        let path = temp_path("durable_replay");
+
+        {
+            let mut bst = DurableBSTMap::<u32, String>::open(&path, FsyncPolicy::Always).unwrap();
+
+            bst.insert(12, "hello".to_string()).unwrap();
+            bst.insert(35, "bye".to_string()).unwrap();
+            bst.insert(999, "test".to_string()).unwrap();
+            bst.insert(35, "bye again".to_string()).unwrap();
+            bst.remove(12).unwrap();
+        }
+
+        let bst = DurableBSTMap::<u32, String>::open(&path, FsyncPolicy::Always).unwrap();
+
+        assert_eq!(bst.len(), 2);
+        assert!(!bst.contains(12));
+        assert_eq!(bst.get(&35).unwrap(), "bye again");
+        assert_eq!(bst.get(&999).unwrap(), "test");
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn clear_is_replayed() {
+        let path = temp_path("durable_clear");
+
+        {
+            let mut bst =
+                DurableBSTMap::<String, u64>::open(&path, FsyncPolicy::EveryN(2)).unwrap();
+
+            bst.insert("a".to_string(), 1).unwrap();
+            bst.insert("b".to_string(), 2).unwrap();
+            bst.clear().unwrap();
+            bst.insert("c".to_string(), 3).unwrap();
+        }
+
+        let bst = DurableBSTMap::<String, u64>::open(&path, FsyncPolicy::Never).unwrap();
+
+        assert_eq!(bst.iter().collect::<Vec<_>>(), vec![(&"c".to_string(), &3)]);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn committed_transaction_is_replayed_as_a_whole() {
+        let path = temp_path("durable_transaction");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Always).unwrap();
+            bst.insert(1, 1).unwrap();
+
+            let mut transaction = bst.transaction();
+            transaction.remove(1);
+            transaction.insert(2, 2);
+            transaction.insert(3, 3);
+            assert!(!transaction.contains(&1));
+            transaction.commit().unwrap();
+
+            let mut transaction = bst.transaction();
+            transaction.insert(4, 4);
+            transaction.rollback();
+        }
+
+        let bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Always).unwrap();
+        assert_eq!(bst.iter().collect::<Vec<_>>(), vec![(&2, &2), (&3, &3)]);
+        drop(bst);
+
+        // Cutting off the end of the batch record drops the whole batch, not just its tail
+        let segment = segment_path(&path, 0);
+        let len = fs::metadata(&segment).unwrap().len();
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&segment)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        let bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Always).unwrap();
+        assert_eq!(bst.iter().collect::<Vec<_>>(), vec![(&1, &1)]);
+
+        drop(bst);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn comparator_is_stored_with_the_map() {
+        let path = temp_path("durable_comparator");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32, _>::open_with_comparator(
+                &path,
+                FsyncPolicy::Never,
+                ReverseComparator,
+            )
+            .unwrap();
+            bst.insert(1, 1).unwrap();
+            bst.insert(2, 2).unwrap();
+            bst.compact().unwrap();
+            bst.insert(3, 3).unwrap();
+        }
+
+        assert!(DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).is_err());
+
+        let bst = DurableBSTMap::<u32, u32, _>::open_with_comparator(
+            &path,
+            FsyncPolicy::Never,
+            ReverseComparator,
+        )
+        .unwrap();
+        assert_eq!(
+            bst.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![3, 2, 1]
+        );
+
+        drop(bst);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn transaction_with_comparator_is_replayed() {
+        let path = temp_path("durable_comparator_transaction");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32, _>::open_with_comparator(
+                &path,
+                FsyncPolicy::Never,
+                ReverseComparator,
+            )
+            .unwrap();
+
+            let mut transaction = bst.transaction();
+            transaction.insert(1, 1);
+            transaction.insert(2, 2);
+            transaction.commit().unwrap();
+        }
+
+        let bst = DurableBSTMap::<u32, u32, _>::open_with_comparator(
+            &path,
+            FsyncPolicy::Never,
+            ReverseComparator,
+        )
+        .unwrap();
+        assert_eq!(bst.iter().map(|(k, _)| *k).collect::<Vec<_>>(), vec![2, 1]);
+
+        drop(bst);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn removal_of_nonexistent_key_is_not_logged() {
+        let path = temp_path("durable_remove_missing");
+
+        let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+        bst.insert(1, 1).unwrap();
+
+        let len_before = bst.log_len();
+
+        assert!(bst.remove(2).unwrap().is_none());
+        assert_eq!(bst.log_len(), len_before);
+
+        drop(bst);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn compaction_replaces_old_log_with_snapshot() {
+        let path = temp_path("durable_compact");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+
+            for i in 0..100 {
+                bst.insert(i % 10, i).unwrap();
+            }
+
+            bst.compact().unwrap();
+
+            assert!(snapshot_path(&path, 1).exists());
+            assert!(!segment_path(&path, 0).exists());
+
+            bst.insert(42, 42).unwrap();
+            bst.remove(0).unwrap();
+        }
+
+        let bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+
+        assert_eq!(bst.len(), 10);
+        assert!(!bst.contains(0));
+        assert_eq!(*bst.get(&9).unwrap(), 99);
+        assert_eq!(*bst.get(&42).unwrap(), 42);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn compaction_is_triggered_by_threshold() {
+        let path = temp_path("durable_threshold");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+            bst.set_compaction_threshold(Some(256));
+
+            for i in 0..200 {
+                bst.insert(i % 5, i).unwrap();
+                assert!(bst.log_len() < 256);
+            }
+        }
+
+        let bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+
+        assert_eq!(
+            bst.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(),
+            vec![(0, 195), (1, 196), (2, 197), (3, 198), (4, 199)]
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn interrupted_compaction_recovers_from_snapshot() {
+        let path = temp_path("durable_interrupted");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+            bst.insert(1, 10).unwrap();
+            bst.insert(2, 20).unwrap();
+            bst.compact().unwrap();
+            bst.insert(3, 30).unwrap();
+        }
+
+        // Pretend the crash happened right after the snapshot was renamed into place,
+        // before the new segment was created and the old files were removed
+        fs::remove_file(segment_path(&path, 1)).unwrap();
+        fs::write(segment_path(&path, 0), b"RBSTWAL1").unwrap();
+
+        let bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+
+        assert_eq!(bst.len(), 2);
+        assert!(!segment_path(&path, 0).exists());
+        assert!(segment_path(&path, 1).exists());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn failed_segment_creation_poisons_the_map() {
+        let path = temp_path("durable_compact_segment_fails");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+            bst.insert(1, 10).unwrap();
+
+            // A directory in place of the new segment makes opening it fail
+            fs::create_dir(segment_path(&path, 1)).unwrap();
+
+            assert!(bst.compact().is_err());
+            assert!(!snapshot_path(&path, 1).exists());
+            assert!(bst.insert(2, 20).is_err());
+            assert!(bst.remove(1).is_err());
+            assert!(bst.clear().is_err());
+            assert!(bst.compact().is_err());
+        }
+
+        fs::remove_dir(segment_path(&path, 1)).unwrap();
+
+        let bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+        assert_eq!(
+            bst.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(),
+            vec![(1, 10)]
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn failed_snapshot_keeps_writing_to_the_new_segment() {
+        let path = temp_path("durable_compact_snapshot_fails");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+            bst.insert(1, 10).unwrap();
+
+            // A directory in place of the temporary file makes writing the snapshot fail
+            fs::create_dir(snapshot_tmp_path(&path, 1)).unwrap();
+
+            assert!(bst.compact().is_err());
+            assert!(!snapshot_path(&path, 1).exists());
+
+            bst.insert(2, 20).unwrap();
+            bst.remove(1).unwrap();
+        }
+
+        fs::remove_dir(snapshot_tmp_path(&path, 1)).unwrap();
+
+        let bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+        assert_eq!(
+            bst.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(),
+            vec![(2, 20)]
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_segments_are_an_error() {
+        let path = temp_path("durable_missing_segment");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+            bst.insert(1, 10).unwrap();
+            bst.compact().unwrap();
+            bst.insert(2, 20).unwrap();
+            bst.compact().unwrap();
+        }
+
+        // Newest snapshot is corrupted and the segments needed to use the older one are gone
+        fs::write(snapshot_path(&path, 2), b"garbage").unwrap();
+
+        assert!(DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).is_err());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn unreadable_snapshot_error_is_returned() {
+        let path = temp_path("durable_snapshot_error");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+            bst.insert(1, 10).unwrap();
+            bst.compact().unwrap();
+        }
+
+        fs::write(snapshot_path(&path, 1), b"garbage").unwrap();
+
+        let Err(err) = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never) else {
+            panic!("map with corrupted snapshot was opened");
+        };
+        assert_eq!(err.to_string(), "file is not a snapshot");
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn stale_snapshot_tmp_file_is_removed() {
+        let path = temp_path("durable_snapshot_tmp");
+
+        {
+            let mut bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+            bst.insert(1, 10).unwrap();
+        }
+
+        // Crash in the middle of writing the snapshot, before it was renamed into place
+        fs::write(snapshot_tmp_path(&path, 1), b"RBSTSN").unwrap();
+
+        let bst = DurableBSTMap::<u32, u32>::open(&path, FsyncPolicy::Never).unwrap();
+        assert_eq!(bst.len(), 1);
+        assert!(!snapshot_tmp_path(&path, 1).exists());
+
+        drop(bst);
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,448 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains BSTMap wrapper with entries which expire after a given time
+//
+// Expired entries are hidden from reads right away, but stay in memory until they are touched
+// by a mutation or removed by sweep(). Sweeping is cheap, since deadlines are kept in a BTreeSet
+// ordered by deadline - it only looks at entries which have actually expired
+//
+// The index is not a BSTMap on purpose: with a constant TTL deadlines only ever grow,
+// which would make it a right spine with O(n) inserts
+
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{BSTMap, iter::BSTMapByrefInorderIter};
+
+// Source of the current time, so that expiry can be tested without sleeping
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+// Clock using the real, monotonic time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// Clock which only moves when told to, clones share the same time
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+// Value along with its deadline, None if it never expires
+struct Entry<V> {
+    value: V,
+    deadline: Option<Instant>,
+}
+
+impl<V> Entry<V> {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.deadline.is_some_and(|deadline| deadline <= now)
+    }
+}
+
+pub struct ExpiringBSTMap<K: Ord + Clone, V, C: Clock = SystemClock> {
+    bst: BSTMap<K, Entry<V>>,
+    // secondary index of entries which have a deadline, ordered by it
+    deadlines: BTreeSet<(Instant, K)>,
+    clock: C,
+}
+
+impl<K: Ord + Clone, V> ExpiringBSTMap<K, V, SystemClock> {
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<K: Ord + Clone, V> Default for ExpiringBSTMap<K, V, SystemClock> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V, C: Clock> ExpiringBSTMap<K, V, C> {
+    pub fn with_clock(clock: C) -> Self {
+        Self {
+            bst: BSTMap::new(),
+            deadlines: BTreeSet::new(),
+            clock,
+        }
+    }
+
+    // Number of entries which have not expired yet
+    pub fn len(&self) -> usize {
+        let now = self.clock.now();
+        let expired = self
+            .deadlines
+            .iter()
+            .take_while(|(deadline, _)| *deadline <= now)
+            .count();
+
+        self.bst.len() - expired
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.bst.clear();
+        self.deadlines.clear();
+    }
+
+    // Inserts an entry which never expires
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_entry(key, value, None)
+    }
+
+    // Inserts an entry which expires after ttl
+    // A ttl too long to be represented (e.g. Duration::MAX) means the entry never expires
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> Option<V> {
+        let deadline = self.clock.now().checked_add(ttl);
+        self.insert_entry(key, value, deadline)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let now = self.clock.now();
+
+        self.bst
+            .get(key)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| &entry.value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.remove_if_expired(key);
+
+        self.bst.get_mut(key).map(|entry| &mut entry.value)
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        let now = self.clock.now();
+        let entry = self.bst.remove(key.clone())?;
+
+        if let Some(deadline) = entry.deadline {
+            self.deadlines.remove(&(deadline, key));
+        }
+
+        (!entry.is_expired(now)).then_some(entry.value)
+    }
+
+    // Time left until the entry expires
+    // None if there is no such entry, Some(None) if the entry never expires
+    pub fn ttl(&self, key: &K) -> Option<Option<Duration>> {
+        let now = self.clock.now();
+        let entry = self.bst.get(key).filter(|entry| !entry.is_expired(now))?;
+
+        Some(entry.deadline.map(|deadline| deadline - now))
+    }
+
+    // Sets a new ttl for an existing entry, returns false if there is no such entry
+    // Same as with insert_with_ttl(), a ttl too long to be represented means the entry never expires
+    pub fn expire(&mut self, key: &K, ttl: Duration) -> bool {
+        self.remove_if_expired(key);
+
+        let deadline = self.clock.now().checked_add(ttl);
+        let Some(entry) = self.bst.get_mut(key) else {
+            return false;
+        };
+
+        if let Some(old_deadline) = std::mem::replace(&mut entry.deadline, deadline) {
+            self.deadlines.remove(&(old_deadline, key.clone()));
+        }
+        if let Some(deadline) = deadline {
+            self.deadlines.insert((deadline, key.clone()));
+        }
+
+        true
+    }
+
+    // Makes the entry never expire, returns false if there is no such entry or it had no ttl
+    pub fn persist(&mut self, key: &K) -> bool {
+        self.remove_if_expired(key);
+
+        let Some(deadline) = self
+            .bst
+            .get_mut(key)
+            .and_then(|entry| entry.deadline.take())
+        else {
+            return false;
+        };
+
+        self.deadlines.remove(&(deadline, key.clone()));
+        true
+    }
+
+    // Removes all expired entries from memory, returns how many were removed
+    pub fn sweep(&mut self) -> usize {
+        let now = self.clock.now();
+        let mut removed = 0;
+
+        while let Some((deadline, _)) = self.deadlines.first() {
+            if *deadline > now {
+                break;
+            }
+
+            // unwrap is safe, the index is not empty
+            let (_, key) = self.deadlines.pop_first().unwrap();
+            self.bst.remove(key);
+            removed += 1;
+        }
+
+        removed
+    }
+
+    // In-order iteration over entries which have not expired
+    pub fn iter(&self) -> ExpiringBSTMapIter<'_, K, V> {
+        ExpiringBSTMapIter {
+            inner: self.bst.iter(),
+            now: self.clock.now(),
+        }
+    }
+
+    fn insert_entry(&mut self, key: K, value: V, deadline: Option<Instant>) -> Option<V> {
+        let now = self.clock.now();
+
+        if let Some(deadline) = deadline {
+            self.deadlines.insert((deadline, key.clone()));
+        }
+
+        let old_entry = self.bst.insert(key.clone(), Entry { value, deadline })?;
+
+        if let Some(old_deadline) = old_entry.deadline {
+            // Same deadline as the new one would remove the fresh index entry
+            if Some(old_deadline) != deadline {
+                self.deadlines.remove(&(old_deadline, key));
+            }
+        }
+
+        (!old_entry.is_expired(now)).then_some(old_entry.value)
+    }
+
+    fn remove_if_expired(&mut self, key: &K) {
+        let now = self.clock.now();
+
+        let deadline = match self.bst.get(key) {
+            Some(entry) if entry.is_expired(now) => entry.deadline,
+            _ => return,
+        };
+
+        self.bst.remove(key.clone());
+        if let Some(deadline) = deadline {
+            self.deadlines.remove(&(deadline, key.clone()));
+        }
+    }
+}
+
+// Iterator over entries of ExpiringBSTMap which have not expired at the moment it was created
+pub struct ExpiringBSTMapIter<'a, K: Ord, V> {
+    inner: BSTMapByrefInorderIter<'a, K, Entry<V>>,
+    now: Instant,
+}
+
+impl<'a, K: Ord, V> Iterator for ExpiringBSTMapIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let now = self.now;
+
+        self.inner
+            .by_ref()
+            .find(|(_, entry)| !entry.is_expired(now))
+            .map(|(key, entry)| (key, &entry.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ExpiringBSTMap, ManualClock};
+
+    fn map_with_clock() -> (ExpiringBSTMap<u32, String, ManualClock>, ManualClock) {
+        let clock = ManualClock::new();
+        (ExpiringBSTMap::with_clock(clock.clone()), clock)
+    }
+
+    #[test]
+    fn entries_disappear_after_ttl() {
+        let (mut bst, clock) = map_with_clock();
+
+        bst.insert_with_ttl(1, "short".to_string(), Duration::from_secs(10));
+        bst.insert_with_ttl(2, "long".to_string(), Duration::from_secs(60));
+        bst.insert(3, "forever".to_string());
+
+        assert_eq!(bst.len(), 3);
+
+        clock.advance(Duration::from_secs(10));
+
+        assert!(bst.get(&1).is_none());
+        assert!(!bst.contains(&1));
+        assert_eq!(bst.get(&2).unwrap(), "long");
+        assert_eq!(bst.len(), 2);
+        assert_eq!(bst.iter().map(|(k, _)| *k).collect::<Vec<_>>(), vec![2, 3]);
+
+        clock.advance(Duration::from_secs(3600));
+
+        assert_eq!(bst.len(), 1);
+        assert_eq!(bst.get(&3).unwrap(), "forever");
+    }
+
+    #[test]
+    fn constant_ttl_with_advancing_clock_does_not_degrade_the_index() {
+        let (mut bst, clock) = map_with_clock();
+
+        // Every deadline is later than all previous ones, a plain BST index would be a single spine
+        // Keys are scrambled, so that the map itself stays shallow
+        for i in 0..100_000u32 {
+            bst.insert_with_ttl(
+                i.wrapping_mul(2_654_435_761),
+                String::new(),
+                Duration::from_secs(60),
+            );
+            clock.advance(Duration::from_millis(1));
+        }
+
+        // Entries inserted during the first 40 seconds (both ends included) are past their deadline
+        assert_eq!(bst.len(), 59_999);
+        assert_eq!(bst.sweep(), 40_001);
+        assert_eq!(bst.len(), 59_999);
+    }
+
+    #[test]
+    fn ttl_reports_remaining_time() {
+        let (mut bst, clock) = map_with_clock();
+
+        bst.insert_with_ttl(1, "a".to_string(), Duration::from_secs(30));
+        bst.insert(2, "b".to_string());
+
+        clock.advance(Duration::from_secs(12));
+
+        assert_eq!(bst.ttl(&1), Some(Some(Duration::from_secs(18))));
+        assert_eq!(bst.ttl(&2), Some(None));
+        assert_eq!(bst.ttl(&3), None);
+    }
+
+    #[test]
+    fn persist_and_expire_change_deadline() {
+        let (mut bst, clock) = map_with_clock();
+
+        bst.insert_with_ttl(1, "a".to_string(), Duration::from_secs(5));
+        bst.insert(2, "b".to_string());
+
+        assert!(bst.persist(&1));
+        assert!(!bst.persist(&1));
+        assert!(bst.expire(&2, Duration::from_secs(5)));
+        assert!(!bst.expire(&3, Duration::from_secs(5)));
+
+        clock.advance(Duration::from_secs(5));
+
+        assert_eq!(bst.get(&1).unwrap(), "a");
+        assert!(bst.get(&2).is_none());
+        assert_eq!(bst.sweep(), 1);
+    }
+
+    #[test]
+    fn ttl_too_long_to_represent_never_expires() {
+        let (mut bst, clock) = map_with_clock();
+
+        bst.insert_with_ttl(1, "a".to_string(), Duration::MAX);
+        bst.insert_with_ttl(2, "b".to_string(), Duration::from_secs(5));
+        assert!(bst.expire(&2, Duration::MAX));
+
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(bst.sweep(), 0);
+
+        assert_eq!(bst.ttl(&1), Some(None));
+        assert_eq!(bst.ttl(&2), Some(None));
+        assert_eq!(bst.len(), 2);
+    }
+
+    #[test]
+    fn sweep_removes_only_expired_entries() {
+        let (mut bst, clock) = map_with_clock();
+
+        for i in 0..10 {
+            bst.insert_with_ttl(i, i.to_string(), Duration::from_secs(i as u64 + 1));
+        }
+
+        clock.advance(Duration::from_secs(4));
+
+        assert_eq!(bst.sweep(), 4);
+        assert_eq!(bst.sweep(), 0);
+        assert_eq!(bst.len(), 6);
+        assert_eq!(bst.iter().next().map(|(k, _)| *k), Some(4));
+    }
+
+    #[test]
+    fn overwriting_entry_replaces_its_deadline() {
+        let (mut bst, clock) = map_with_clock();
+
+        bst.insert_with_ttl(1, "old".to_string(), Duration::from_secs(5));
+        assert_eq!(bst.insert(1, "new".to_string()), Some("old".to_string()));
+
+        clock.advance(Duration::from_secs(10));
+
+        assert_eq!(bst.sweep(), 0);
+        assert_eq!(bst.get(&1).unwrap(), "new");
+
+        // Overwriting an expired entry does not return its value
+        bst.insert_with_ttl(2, "gone".to_string(), Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
+
+        assert!(bst.insert(2, "fresh".to_string()).is_none());
+        assert!(bst.remove(1).is_some());
+        assert_eq!(bst.len(), 1);
+    }
+}
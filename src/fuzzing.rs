@@ -0,0 +1,61 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains entry points for the fuzz targets in fuzz/ (behind "fuzzing" feature), not a stable API
+//
+// Snapshots and log segments are normally only read from files inside DurableBSTMap's directory,
+// these functions read them straight from bytes instead. With fix_checksums the checksums in the bytes
+// are replaced with correct ones first - a fuzzer would hardly ever guess a CRC-32, and without it
+// nothing past the checksum check would get fuzzed
+
+use std::io;
+
+use crate::{BSTMap, Decode, OrdComparator, codec::crc32, snapshot, wal};
+
+// Same as reading a snapshot file with the given contents
+pub fn decode_snapshot<K: Decode + Ord, V: Decode>(
+    contents: &[u8],
+    fix_checksum: bool,
+) -> io::Result<BSTMap<K, V>> {
+    let mut contents = contents.to_vec();
+
+    // checksum is the last 4 bytes
+    if fix_checksum && contents.len() >= 4 {
+        let body_len = contents.len() - 4;
+        let checksum = crc32(&contents[..body_len]);
+        contents[body_len..].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    snapshot::decode_snapshot(&contents, OrdComparator)
+}
+
+// Same as replaying a log segment with the given contents,
+// along with the length of its valid part (the rest being a torn record)
+pub fn replay_log<K: Decode + Ord, V: Decode>(
+    contents: &[u8],
+    fix_checksums: bool,
+) -> io::Result<(BSTMap<K, V>, usize)> {
+    let mut contents = contents.to_vec();
+
+    if fix_checksums {
+        wal::fix_checksums(&mut contents);
+    }
+
+    let mut bst = BSTMap::new();
+    let valid_len = wal::replay_log(&contents, &mut bst)?;
+
+    Ok((bst, valid_len))
+}
@@ -0,0 +1,574 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains a flat, read-only image of BSTMap which can be queried in place
+// (for example straight out of a memory mapped file) without building any Nodes
+//
+// Layout of the image:
+// - 8 bytes of magic (IMAGE_MAGIC)
+// - u64 number of entries
+// - u64 offset of the root node (IMAGE_NIL if the image is empty)
+// - u32 length of the comparator name, followed by the name (see Comparator::name())
+// - nodes, each being:
+//   - u64 offset of the left child, u64 offset of the right child (IMAGE_NIL if there is none)
+//   - u32 length of the key, u32 length of the value
+//   - encoded key followed by encoded value
+//
+// Nodes are stored in key order and linked into a balanced tree (whatever the shape of the source map was),
+// so lookups take O(log n) and in-order iteration is a sequential scan of the file
+//
+// The bytes are not trusted - every offset is bounds checked and every failure is reported as io::Error
+// Image can only be opened with a comparator of the same name as the one used by the map it was written from
+
+use std::{
+    io::{self, BufWriter, Write},
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    BSTMap, Comparator, OrdComparator,
+    codec::{ByteReader, DecodeRef, Encode, invalid_data},
+    comparator::{is_after_start, is_before_end},
+};
+
+const IMAGE_MAGIC: &[u8; 8] = b"RBSTIMG2";
+const IMAGE_NIL: u64 = u64::MAX;
+// Length of the header up to and including the comparator name length
+const IMAGE_HEADER_LEN: usize = 28;
+const NODE_HEADER_LEN: usize = 24;
+
+impl<K: Encode, V: Encode, C: Comparator<K>> BSTMap<K, V, C> {
+    // Writes the contents of the map as an image which can be read with BSTMapImage
+    pub fn write_image<W: Write>(&self, out: W) -> io::Result<()> {
+        let entry_count = self.len();
+
+        // Encode all entries first, offsets of the nodes depend on their sizes
+        // spans contain (start of the key in data, key length, value length)
+        let mut data = Vec::new();
+        let mut spans = Vec::with_capacity(entry_count);
+        for (key, value) in self.iter_inorder() {
+            let start = data.len();
+            key.encode(&mut data);
+            let key_len = data.len() - start;
+            value.encode(&mut data);
+            let value_len = data.len() - start - key_len;
+
+            if key_len > u32::MAX as usize || value_len > u32::MAX as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "key or value too large for an image",
+                ));
+            }
+
+            spans.push((start, key_len, value_len));
+        }
+
+        let comparator_name = self.comparator().name().as_bytes();
+        let nodes_start = IMAGE_HEADER_LEN + comparator_name.len();
+
+        let mut offsets = Vec::with_capacity(entry_count);
+        let mut offset = nodes_start as u64;
+        for (_, key_len, value_len) in &spans {
+            offsets.push(offset);
+            offset += (NODE_HEADER_LEN + key_len + value_len) as u64;
+        }
+
+        // Root of every range [lo, hi) of sorted entries is its middle element,
+        // which makes the tree balanced no matter in which order keys were inserted
+        let mut left = vec![IMAGE_NIL; entry_count];
+        let mut right = vec![IMAGE_NIL; entry_count];
+        let mut ranges = vec![(0, entry_count)];
+
+        while let Some((lo, hi)) = ranges.pop() {
+            if lo >= hi {
+                continue;
+            }
+
+            let mid = lo + (hi - lo) / 2;
+
+            if lo < mid {
+                left[mid] = offsets[lo + (mid - lo) / 2];
+            }
+
+            if mid + 1 < hi {
+                right[mid] = offsets[mid + 1 + (hi - mid - 1) / 2];
+            }
+
+            ranges.push((lo, mid));
+            ranges.push((mid + 1, hi));
+        }
+
+        let root = if entry_count == 0 {
+            IMAGE_NIL
+        } else {
+            offsets[entry_count / 2]
+        };
+
+        let mut out = BufWriter::new(out);
+        out.write_all(IMAGE_MAGIC)?;
+        out.write_all(&(entry_count as u64).to_le_bytes())?;
+        out.write_all(&root.to_le_bytes())?;
+        out.write_all(&(comparator_name.len() as u32).to_le_bytes())?;
+        out.write_all(comparator_name)?;
+
+        for (i, (start, key_len, value_len)) in spans.into_iter().enumerate() {
+            out.write_all(&left[i].to_le_bytes())?;
+            out.write_all(&right[i].to_le_bytes())?;
+            out.write_all(&(key_len as u32).to_le_bytes())?;
+            out.write_all(&(value_len as u32).to_le_bytes())?;
+            out.write_all(&data[start..start + key_len + value_len])?;
+        }
+
+        out.flush()
+    }
+}
+
+// Node of the image, with key and value still as bytes
+struct ImageNode<'a> {
+    left: u64,
+    right: u64,
+    key: &'a [u8],
+    value: &'a [u8],
+    // offset of the node stored right after this one, which is its in-order successor
+    next: usize,
+}
+
+fn read_node(bytes: &[u8], nodes_start: usize, offset: u64) -> io::Result<ImageNode<'_>> {
+    let start = usize::try_from(offset)
+        .ok()
+        .filter(|start| *start >= nodes_start && *start < bytes.len())
+        .ok_or_else(|| invalid_data("node offset out of bounds in image"))?;
+
+    let mut reader = ByteReader::new(&bytes[start..]);
+    let left = reader.read_u64()?;
+    let right = reader.read_u64()?;
+    let key_len = reader.read_u32()? as usize;
+    let value_len = reader.read_u32()? as usize;
+    let key = reader.read_bytes(key_len)?;
+    let value = reader.read_bytes(value_len)?;
+
+    Ok(ImageNode {
+        left,
+        right,
+        key,
+        value,
+        next: start + reader.position(),
+    })
+}
+
+// Read-only view of an image written by BSTMap::write_image()
+// K and V are decoded from the bytes on every access, use borrowing types
+// such as &str or &[u8] to avoid copying anything
+pub struct BSTMapImage<'a, K, V, C = OrdComparator> {
+    bytes: &'a [u8],
+    len: usize,
+    root: u64,
+    nodes_start: usize,
+    comparator: C,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'a, K: DecodeRef<'a> + Ord, V: DecodeRef<'a>> BSTMapImage<'a, K, V> {
+    pub fn new(bytes: &'a [u8]) -> io::Result<Self> {
+        Self::with_comparator(bytes, OrdComparator)
+    }
+}
+
+impl<'a, K: DecodeRef<'a>, V: DecodeRef<'a>, C: Comparator<K>> BSTMapImage<'a, K, V, C> {
+    // Opens an image of a map which used comparator (or one with the same name)
+    pub fn with_comparator(bytes: &'a [u8], comparator: C) -> io::Result<Self> {
+        if !bytes.starts_with(IMAGE_MAGIC) {
+            return Err(invalid_data("bytes are not an image of BSTMap"));
+        }
+
+        let mut reader = ByteReader::new(&bytes[IMAGE_MAGIC.len()..]);
+        let len = reader.read_u64()?;
+        let root = reader.read_u64()?;
+        let name_len = reader.read_u32()? as usize;
+        let name = reader.read_bytes(name_len)?;
+
+        if name != comparator.name().as_bytes() {
+            return Err(invalid_data(
+                "image was written with a different comparator",
+            ));
+        }
+
+        let nodes_start = IMAGE_HEADER_LEN + name_len;
+
+        // Every node takes at least NODE_HEADER_LEN bytes, which bounds the length
+        let max_len = (bytes.len() - nodes_start) / NODE_HEADER_LEN;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= max_len)
+            .ok_or_else(|| invalid_data("entry count in image is too large"))?;
+
+        if (len == 0) != (root == IMAGE_NIL) {
+            return Err(invalid_data("root of image does not match its entry count"));
+        }
+
+        Ok(Self {
+            bytes,
+            len,
+            root,
+            nodes_start,
+            comparator,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> io::Result<Option<V>> {
+        let mut offset = self.root;
+        let mut steps = 0;
+
+        while offset != IMAGE_NIL {
+            // A path longer than the number of entries means that the links form a cycle
+            steps += 1;
+            if steps > self.len {
+                return Err(invalid_data("cycle in image"));
+            }
+
+            let node = read_node(self.bytes, self.nodes_start, offset)?;
+
+            offset = match self.comparator.compare(&K::decode_ref(node.key)?, key) {
+                std::cmp::Ordering::Less => node.right,
+                std::cmp::Ordering::Greater => node.left,
+                std::cmp::Ordering::Equal => return V::decode_ref(node.value).map(Some),
+            };
+        }
+
+        Ok(None)
+    }
+
+    pub fn contains(&self, key: &K) -> io::Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    // In-order iteration over all entries
+    pub fn iter(&self) -> BSTMapImageIter<'a, K, V, C>
+    where
+        C: Clone,
+    {
+        let next = if self.is_empty() {
+            None
+        } else {
+            Some(self.nodes_start)
+        };
+
+        BSTMapImageIter {
+            bytes: self.bytes,
+            nodes_start: self.nodes_start,
+            next,
+            remaining: self.len,
+            end: Bound::Unbounded,
+            comparator: self.comparator.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    // In-order iteration over entries with keys within range
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> io::Result<BSTMapImageIter<'a, K, V, C>>
+    where
+        K: Clone,
+        C: Clone,
+    {
+        // Find the first node which is not below the start of the range
+        let mut first = None;
+        let mut offset = self.root;
+        let mut steps = 0;
+
+        while offset != IMAGE_NIL {
+            steps += 1;
+            if steps > self.len {
+                return Err(invalid_data("cycle in image"));
+            }
+
+            let node = read_node(self.bytes, self.nodes_start, offset)?;
+            let key = K::decode_ref(node.key)?;
+
+            offset = if is_after_start(&self.comparator, &key, range.start_bound()) {
+                first = Some(offset);
+                node.left
+            } else {
+                node.right
+            };
+        }
+
+        // From there on nodes are read sequentially, until one is past the end of the range
+        let next = first.map(|offset| offset as usize);
+
+        Ok(BSTMapImageIter {
+            bytes: self.bytes,
+            nodes_start: self.nodes_start,
+            next,
+            remaining: self.len,
+            end: range.end_bound().cloned(),
+            comparator: self.comparator.clone(),
+            _marker: PhantomData,
+        })
+    }
+}
+
+// In-order iterator over BSTMapImage, reading nodes one after another
+// Yields an error (and then stops) if the image turns out to be corrupted
+pub struct BSTMapImageIter<'a, K, V, C = OrdComparator> {
+    bytes: &'a [u8],
+    nodes_start: usize,
+    next: Option<usize>,
+    // upper limit of nodes left, protects from looping over garbage after the last node
+    remaining: usize,
+    end: Bound<K>,
+    comparator: C,
+    _marker: PhantomData<fn() -> V>,
+}
+
+impl<'a, K: DecodeRef<'a>, V: DecodeRef<'a>, C: Comparator<K>> Iterator
+    for BSTMapImageIter<'a, K, V, C>
+{
+    type Item = io::Result<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.next.take()?;
+
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let decoded = read_node(self.bytes, self.nodes_start, offset as u64).and_then(|node| {
+            let key = K::decode_ref(node.key)?;
+            let value = V::decode_ref(node.value)?;
+            Ok((key, value, node.next))
+        });
+
+        let (key, value, next) = match decoded {
+            Ok(decoded) => decoded,
+            Err(err) => return Some(Err(err)),
+        };
+
+        if !is_before_end(&self.comparator, &key, self.end.as_ref()) {
+            return None;
+        }
+
+        if next < self.bytes.len() {
+            self.next = Some(next);
+        }
+
+        Some(Ok((key, value)))
+    }
+}
+
+// Image backed by a memory mapped file
+#[cfg(feature = "mmap")]
+pub struct BSTMapImageFile {
+    mmap: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl BSTMapImageFile {
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+
+        // Safety: the mapping is read-only, modifying the file while it is mapped is the caller's problem
+        // (same as with any other mmap) - the view never trusts the bytes, so it can only produce errors
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+
+        Ok(Self { mmap })
+    }
+
+    pub fn view<'a, K: DecodeRef<'a> + Ord, V: DecodeRef<'a>>(
+        &'a self,
+    ) -> io::Result<BSTMapImage<'a, K, V>> {
+        BSTMapImage::new(&self.mmap)
+    }
+
+    pub fn view_with_comparator<'a, K: DecodeRef<'a>, V: DecodeRef<'a>, C: Comparator<K>>(
+        &'a self,
+        comparator: C,
+    ) -> io::Result<BSTMapImage<'a, K, V, C>> {
+        BSTMapImage::with_comparator(&self.mmap, comparator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::{BSTMapImage, BSTMapImageIter, IMAGE_HEADER_LEN};
+    use crate::{BSTMap, ReverseComparator};
+
+    fn keys<'a>(iter: BSTMapImageIter<'a, &'a str, &'a str>) -> Vec<&'a str> {
+        iter.map(|entry| entry.unwrap().0).collect()
+    }
+
+    fn sample_image() -> Vec<u8> {
+        let bst: BSTMap<String, String> = [
+            ("m", "hello"),
+            ("c", "bye"),
+            ("x", "test"),
+            ("a", "test2"),
+            ("e", "high number"),
+            ("z", "utf8 string ąąąąą"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .into();
+
+        let mut image = Vec::new();
+        bst.write_image(&mut image).unwrap();
+        image
+    }
+
+    #[test]
+    fn empty_map_gives_empty_image() {
+        let bst = BSTMap::<u32, u32>::new();
+        let mut bytes = Vec::new();
+        bst.write_image(&mut bytes).unwrap();
+
+        let image = BSTMapImage::<u32, u32>::new(&bytes).unwrap();
+
+        assert!(image.is_empty());
+        assert!(image.get(&1).unwrap().is_none());
+        assert_eq!(image.iter().count(), 0);
+        assert_eq!(image.range(0..10).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn lookups_are_done_in_place() {
+        let bytes = sample_image();
+        let image = BSTMapImage::<&str, &str>::new(&bytes).unwrap();
+
+        assert_eq!(image.len(), 6);
+        assert_eq!(image.get(&"x").unwrap(), Some("test"));
+        assert_eq!(image.get(&"z").unwrap(), Some("utf8 string ąąąąą"));
+        assert!(image.get(&"b").unwrap().is_none());
+        assert!(image.contains(&"a").unwrap());
+
+        // keys can also be looked at as raw bytes
+        let raw = BSTMapImage::<&[u8], &[u8]>::new(&bytes).unwrap();
+        assert_eq!(
+            raw.get(&b"m".as_slice()).unwrap(),
+            Some(b"hello".as_slice())
+        );
+    }
+
+    #[test]
+    fn iteration_is_in_order() {
+        let bytes = sample_image();
+        let image = BSTMapImage::<&str, &str>::new(&bytes).unwrap();
+
+        assert_eq!(keys(image.iter()), vec!["a", "c", "e", "m", "x", "z"]);
+    }
+
+    #[test]
+    fn range_respects_bounds() {
+        let bytes = sample_image();
+        let image = BSTMapImage::<&str, &str>::new(&bytes).unwrap();
+
+        assert_eq!(keys(image.range("c".."x").unwrap()), vec!["c", "e", "m"]);
+        assert_eq!(
+            keys(image.range("b"..="x").unwrap()),
+            vec!["c", "e", "m", "x"]
+        );
+        assert_eq!(keys(image.range("n"..).unwrap()), vec!["x", "z"]);
+        assert_eq!(keys(image.range(.."c").unwrap()), vec!["a"]);
+        assert!(keys(image.range("zz"..).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn image_of_degenerate_tree_is_balanced() {
+        // Inserting sorted keys gives a linked list, the image should still be shallow
+        let bst: BSTMap<u32, u32> = (0..1000).map(|i| (i, i * 2)).collect();
+        let mut bytes = Vec::new();
+        bst.write_image(&mut bytes).unwrap();
+
+        let image = BSTMapImage::<u32, u32>::new(&bytes).unwrap();
+
+        for i in 0..1000 {
+            assert_eq!(image.get(&i).unwrap(), Some(i * 2));
+        }
+        assert_eq!(image.range(500..510).unwrap().count(), 10);
+    }
+
+    #[test]
+    fn corrupted_image_gives_errors_instead_of_panics() {
+        let mut bytes = sample_image();
+
+        // Truncated image
+        let truncated = &bytes[..bytes.len() - 4];
+        let image = BSTMapImage::<&str, &str>::new(truncated).unwrap();
+        assert!(image.iter().any(|entry| entry.is_err()));
+
+        // Root pointing outside of the image
+        bytes[16..24].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        let image = BSTMapImage::<&str, &str>::new(&bytes).unwrap();
+        assert!(image.get(&"a").is_err());
+
+        // Root pointing to itself, first node comes right after the name of the comparator
+        let first_node = IMAGE_HEADER_LEN + "ord".len();
+        bytes[16..24].copy_from_slice(&(first_node as u64).to_le_bytes());
+        bytes[first_node..first_node + 8].copy_from_slice(&(first_node as u64).to_le_bytes());
+        let image = BSTMapImage::<&str, &str>::new(&bytes).unwrap();
+        assert!(image.get(&"0").is_err());
+
+        assert!(BSTMapImage::<&str, &str>::new(b"RBSTIMG").is_err());
+        assert!(BSTMapImage::<&str, &str>::new(b"not an image at all, really").is_err());
+    }
+
+    #[test]
+    fn image_keeps_order_of_the_comparator() {
+        let bst: BSTMap<u32, u32, ReverseComparator> = (0..100).map(|i| (i, i * 2)).collect();
+        let mut bytes = Vec::new();
+        bst.write_image(&mut bytes).unwrap();
+
+        assert!(BSTMapImage::<u32, u32>::new(&bytes).is_err());
+
+        let image = BSTMapImage::<u32, u32, _>::with_comparator(&bytes, ReverseComparator).unwrap();
+
+        assert_eq!(image.get(&42).unwrap(), Some(84));
+        assert_eq!(
+            image
+                .range((Bound::Included(10), Bound::Included(7)))
+                .unwrap()
+                .map(|entry| entry.unwrap().0)
+                .collect::<Vec<_>>(),
+            vec![10, 9, 8, 7]
+        );
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn image_can_be_memory_mapped() {
+        let path = crate::durable::tests::temp_path("image_mmap");
+        std::fs::write(&path, sample_image()).unwrap();
+
+        {
+            let file = super::BSTMapImageFile::open(&path).unwrap();
+            let image = file.view::<&str, &str>().unwrap();
+
+            assert_eq!(image.get(&"e").unwrap(), Some("high number"));
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
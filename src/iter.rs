@@ -14,9 +14,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::collections::VecDeque;
+use std::{collections::VecDeque, iter::FusedIterator, ops::RangeBounds};
 
-use super::{BSTMap, Node};
+use super::{
+    BSTMap, Comparator, Node,
+    comparator::{is_after_start, is_before_end},
+};
 
 // what parts of the node have been visited - nothing, left subtree, node itself, right subtree
 // used by BSTMapByrefInorderIterator to add data about visited nodes to otherwise immutable tree
@@ -28,16 +31,16 @@ pub(crate) enum Visited {
 }
 
 // Implements In-Order iteration over the BST
-pub struct BSTMapByrefInorderIter<'a, K: Ord, V> {
+pub struct BSTMapByrefInorderIter<'a, K, V> {
     pub(crate) stack: Vec<(&'a Node<K, V>, Visited)>,
 }
 
-impl<'a, K: Ord, V> BSTMapByrefInorderIter<'a, K, V> {
-    pub(crate) fn new(bst: &'a BSTMap<K, V>) -> Self {
+impl<'a, K, V> BSTMapByrefInorderIter<'a, K, V> {
+    pub(crate) fn new<C>(bst: &'a BSTMap<K, V, C>) -> Self {
         let stack = match &bst.head {
             None => Vec::new(),
             Some(inner_node) => {
-                let mut s = Vec::with_capacity(bst.len());
+                let mut s = Vec::with_capacity(bst.length);
                 s.push((inner_node.as_ref(), Visited::None));
                 s
             }
@@ -46,7 +49,7 @@ impl<'a, K: Ord, V> BSTMapByrefInorderIter<'a, K, V> {
     }
 }
 
-impl<'a, K: 'a + Ord, V: 'a> Iterator for BSTMapByrefInorderIter<'a, K, V> {
+impl<'a, K: 'a, V: 'a> Iterator for BSTMapByrefInorderIter<'a, K, V> {
     type Item = (&'a K, &'a V);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -84,8 +87,13 @@ impl<'a, K: 'a + Ord, V: 'a> Iterator for BSTMapByrefInorderIter<'a, K, V> {
     }
 }
 
-impl<'a, K: 'a + Ord, V: 'a> From<&'a BSTMap<K, V>> for BSTMapByrefInorderIter<'a, K, V> {
-    fn from(value: &'a BSTMap<K, V>) -> Self {
+// Once the stack is empty, it stays empty
+impl<'a, K: 'a, V: 'a> FusedIterator for BSTMapByrefInorderIter<'a, K, V> {}
+
+impl<'a, K: 'a, V: 'a, C: Comparator<K>> From<&'a BSTMap<K, V, C>>
+    for BSTMapByrefInorderIter<'a, K, V>
+{
+    fn from(value: &'a BSTMap<K, V, C>) -> Self {
         Self::new(value)
     }
 }
@@ -102,13 +110,13 @@ type LeftKVMutRight<'a, K, V> = (
     OptionalKVMut<'a, K, V>,
     OptionalSubtree<'a, K, V>,
 );
-pub struct BSTMapByrefInorderIterMut<'a, K: 'a + Ord, V: 'a> {
+pub struct BSTMapByrefInorderIterMut<'a, K: 'a, V: 'a> {
     pub(crate) stack: Vec<LeftKVMutRight<'a, K, V>>,
 }
 
-impl<'a, K: 'a + Ord, V: 'a> BSTMapByrefInorderIterMut<'a, K, V> {
-    pub(crate) fn new(bst: &'a mut BSTMap<K, V>) -> Self {
-        let bst_len = bst.len();
+impl<'a, K: 'a, V: 'a> BSTMapByrefInorderIterMut<'a, K, V> {
+    pub(crate) fn new<C>(bst: &'a mut BSTMap<K, V, C>) -> Self {
+        let bst_len = bst.length;
         let stack = match &mut bst.head {
             None => Vec::new(),
             Some(inner_node) => {
@@ -133,7 +141,7 @@ impl<'a, K: 'a + Ord, V: 'a> BSTMapByrefInorderIterMut<'a, K, V> {
     }
 }
 
-impl<'a, K: 'a + Ord, V: 'a> Iterator for BSTMapByrefInorderIterMut<'a, K, V> {
+impl<'a, K: 'a, V: 'a> Iterator for BSTMapByrefInorderIterMut<'a, K, V> {
     type Item = (&'a K, &'a mut V);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -185,23 +193,27 @@ impl<'a, K: 'a + Ord, V: 'a> Iterator for BSTMapByrefInorderIterMut<'a, K, V> {
     }
 }
 
-impl<'a, K: 'a + Ord, V: 'a> From<&'a mut BSTMap<K, V>> for BSTMapByrefInorderIterMut<'a, K, V> {
-    fn from(value: &'a mut BSTMap<K, V>) -> Self {
+impl<'a, K: 'a, V: 'a> FusedIterator for BSTMapByrefInorderIterMut<'a, K, V> {}
+
+impl<'a, K: 'a, V: 'a, C: Comparator<K>> From<&'a mut BSTMap<K, V, C>>
+    for BSTMapByrefInorderIterMut<'a, K, V>
+{
+    fn from(value: &'a mut BSTMap<K, V, C>) -> Self {
         Self::new(value)
     }
 }
 
 // Implements breadth-first iterator over BSTMap
-pub struct BSTMapByrefBreadthfirstIter<'a, K: Ord, V> {
+pub struct BSTMapByrefBreadthfirstIter<'a, K, V> {
     pub(crate) queue: VecDeque<&'a Node<K, V>>,
 }
 
-impl<'a, K: Ord, V> BSTMapByrefBreadthfirstIter<'a, K, V> {
-    pub(crate) fn new(bst: &'a BSTMap<K, V>) -> Self {
+impl<'a, K, V> BSTMapByrefBreadthfirstIter<'a, K, V> {
+    pub(crate) fn new<C>(bst: &'a BSTMap<K, V, C>) -> Self {
         let queue = match &bst.head {
             None => VecDeque::new(),
             Some(inner_node) => {
-                let mut q = VecDeque::with_capacity(bst.len());
+                let mut q = VecDeque::with_capacity(bst.length);
                 q.push_back(inner_node.as_ref());
                 q
             }
@@ -209,12 +221,9 @@ impl<'a, K: Ord, V> BSTMapByrefBreadthfirstIter<'a, K, V> {
 
         Self { queue }
     }
-}
-
-impl<'a, K: 'a + Ord, V: 'a> Iterator for BSTMapByrefBreadthfirstIter<'a, K, V> {
-    type Item = (&'a K, &'a V);
 
-    fn next(&mut self) -> Option<Self::Item> {
+    // Once all nodes of a level are returned, the queue holds exactly the nodes of the next level
+    pub(crate) fn next_node(&mut self) -> Option<&'a Node<K, V>> {
         let next_element = self.queue.pop_front();
 
         // safe due to if
@@ -228,23 +237,113 @@ impl<'a, K: 'a + Ord, V: 'a> Iterator for BSTMapByrefBreadthfirstIter<'a, K, V>
             self.queue.push_back(right_node.as_ref());
         }
 
-        Some((&next_node.key, &next_node.value))
+        Some(next_node)
     }
 }
 
-impl<'a, K: 'a + Ord, V: 'a> From<&'a BSTMap<K, V>> for BSTMapByrefBreadthfirstIter<'a, K, V> {
-    fn from(value: &'a BSTMap<K, V>) -> Self {
+impl<'a, K: 'a, V: 'a> Iterator for BSTMapByrefBreadthfirstIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_node().map(|node| (&node.key, &node.value))
+    }
+}
+
+impl<'a, K: 'a, V: 'a, C: Comparator<K>> From<&'a BSTMap<K, V, C>>
+    for BSTMapByrefBreadthfirstIter<'a, K, V>
+{
+    fn from(value: &'a BSTMap<K, V, C>) -> Self {
         Self::new(value)
     }
 }
 
+// Implements In-Order iteration over the part of BST with keys within a range
+// Stack holds nodes whose key is yet to be returned, each one with its left subtree already explored
+pub struct BSTMapByrefRangeIter<'a, K, V> {
+    pub(crate) stack: Vec<&'a Node<K, V>>,
+    // last node within the range, iteration stops after it is returned
+    pub(crate) last: Option<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> BSTMapByrefRangeIter<'a, K, V> {
+    pub(crate) fn new<C: Comparator<K>, R: RangeBounds<K>>(
+        bst: &'a BSTMap<K, V, C>,
+        range: R,
+    ) -> Self {
+        // Find the path to the first node within the start bound
+        let mut stack = Vec::new();
+        let mut current_node = &bst.head;
+
+        while let Some(inner) = current_node.as_ref() {
+            current_node = if is_after_start(&bst.comparator, &inner.key, range.start_bound()) {
+                stack.push(inner.as_ref());
+                &inner.left
+            } else {
+                &inner.right
+            };
+        }
+
+        // Find the last node within the end bound
+        let mut last = None;
+        let mut current_node = &bst.head;
+
+        while let Some(inner) = current_node.as_ref() {
+            current_node = if is_before_end(&bst.comparator, &inner.key, range.end_bound()) {
+                last = Some(inner.as_ref());
+                &inner.right
+            } else {
+                &inner.left
+            };
+        }
+
+        // If the first node is past the last one, the range is empty
+        let is_empty = match (stack.last(), last) {
+            (Some(first), Some(last)) => bst.comparator.compare(&first.key, &last.key).is_gt(),
+            _ => true,
+        };
+
+        if is_empty {
+            return Self {
+                stack: Vec::new(),
+                last: None,
+            };
+        }
+
+        Self { stack, last }
+    }
+}
+
+impl<'a, K: 'a, V: 'a> Iterator for BSTMapByrefRangeIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current_node = self.stack.pop()?;
+
+        if self
+            .last
+            .is_some_and(|last| std::ptr::eq(last, current_node))
+        {
+            self.stack.clear();
+        } else {
+            // Successor is the leftmost node of the right subtree (or the next node on the stack)
+            let mut next_node = &current_node.right;
+            while let Some(inner) = next_node.as_ref() {
+                self.stack.push(inner.as_ref());
+                next_node = &inner.left;
+            }
+        }
+
+        Some((&current_node.key, &current_node.value))
+    }
+}
+
 pub struct BSTMapConsumingInorderIter<K, V> {
     pub(crate) stack: Vec<Box<Node<K, V>>>,
 }
 
-impl<K: Ord, V> BSTMapConsumingInorderIter<K, V> {
-    pub(crate) fn new(mut bst: BSTMap<K, V>) -> Self {
-        let bst_len = bst.len();
+impl<K, V> BSTMapConsumingInorderIter<K, V> {
+    pub(crate) fn new<C>(mut bst: BSTMap<K, V, C>) -> Self {
+        let bst_len = bst.length;
         let stack = match bst.head.take() {
             None => Vec::new(),
             Some(inner_node) => {
@@ -258,7 +357,7 @@ impl<K: Ord, V> BSTMapConsumingInorderIter<K, V> {
     }
 }
 
-impl<K: Ord, V> Iterator for BSTMapConsumingInorderIter<K, V> {
+impl<K, V> Iterator for BSTMapConsumingInorderIter<K, V> {
     type Item = (K, V);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -288,13 +387,60 @@ impl<K: Ord, V> Iterator for BSTMapConsumingInorderIter<K, V> {
     }
 }
 
-impl<K: Ord, V> From<BSTMap<K, V>> for BSTMapConsumingInorderIter<K, V> {
-    fn from(value: BSTMap<K, V>) -> Self {
+impl<K, V> FusedIterator for BSTMapConsumingInorderIter<K, V> {}
+
+impl<K, V, C: Comparator<K>> From<BSTMap<K, V, C>> for BSTMapConsumingInorderIter<K, V> {
+    fn from(value: BSTMap<K, V, C>) -> Self {
         Self::new(value)
     }
 }
 
-impl<'a, K: Ord, V> IntoIterator for &'a BSTMap<K, V> {
+// Iterators over keys or values alone, wrapping the In-Order iterators above
+// The wrapped iterators are neither double-ended nor exact-size, so these are not either
+macro_rules! projection_iter {
+    ($name:ident<$($lt:lifetime,)? K, V>, $base:ty, $item:ty, |$entry:pat_param| $project:expr) => {
+        pub struct $name<$($lt,)? K, V> {
+            pub(crate) inner: $base,
+        }
+
+        impl<$($lt,)? K, V> Iterator for $name<$($lt,)? K, V> {
+            type Item = $item;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.inner.next().map(|$entry| $project)
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.inner.size_hint()
+            }
+        }
+
+        impl<$($lt,)? K, V> FusedIterator for $name<$($lt,)? K, V> {}
+    };
+}
+
+projection_iter!(
+    BSTMapKeysIter<'a, K, V>,
+    BSTMapByrefInorderIter<'a, K, V>,
+    &'a K,
+    |(key, _)| key
+);
+projection_iter!(
+    BSTMapValuesIter<'a, K, V>,
+    BSTMapByrefInorderIter<'a, K, V>,
+    &'a V,
+    |(_, value)| value
+);
+projection_iter!(
+    BSTMapValuesMutIter<'a, K, V>,
+    BSTMapByrefInorderIterMut<'a, K, V>,
+    &'a mut V,
+    |(_, value)| value
+);
+projection_iter!(BSTMapIntoKeysIter<K, V>, BSTMapConsumingInorderIter<K, V>, K, |(key, _)| key);
+projection_iter!(BSTMapIntoValuesIter<K, V>, BSTMapConsumingInorderIter<K, V>, V, |(_, value)| value);
+
+impl<'a, K, V, C: Comparator<K>> IntoIterator for &'a BSTMap<K, V, C> {
     type Item = (&'a K, &'a V);
 
     type IntoIter = BSTMapByrefInorderIter<'a, K, V>;
@@ -304,7 +450,7 @@ impl<'a, K: Ord, V> IntoIterator for &'a BSTMap<K, V> {
     }
 }
 
-impl<'a, K: Ord, V> IntoIterator for &'a mut BSTMap<K, V> {
+impl<'a, K, V, C: Comparator<K>> IntoIterator for &'a mut BSTMap<K, V, C> {
     type Item = (&'a K, &'a mut V);
 
     type IntoIter = BSTMapByrefInorderIterMut<'a, K, V>;
@@ -314,7 +460,7 @@ impl<'a, K: Ord, V> IntoIterator for &'a mut BSTMap<K, V> {
     }
 }
 
-impl<K: Ord, V> IntoIterator for BSTMap<K, V> {
+impl<K, V, C: Comparator<K>> IntoIterator for BSTMap<K, V, C> {
     type Item = (K, V);
 
     type IntoIter = BSTMapConsumingInorderIter<K, V>;
@@ -324,9 +470,9 @@ impl<K: Ord, V> IntoIterator for BSTMap<K, V> {
     }
 }
 
-impl<K: Ord, V> FromIterator<(K, V)> for BSTMap<K, V> {
+impl<K, V, C: Comparator<K> + Default> FromIterator<(K, V)> for BSTMap<K, V, C> {
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
-        let mut bst = Self::new();
+        let mut bst = Self::default();
 
         for (k, v) in iter {
             bst.insert(k, v);
@@ -336,9 +482,9 @@ impl<K: Ord, V> FromIterator<(K, V)> for BSTMap<K, V> {
     }
 }
 
-impl<K: Ord, V, const N: usize> From<[(K, V); N]> for BSTMap<K, V> {
+impl<K, V, C: Comparator<K> + Default, const N: usize> From<[(K, V); N]> for BSTMap<K, V, C> {
     fn from(array: [(K, V); N]) -> Self {
-        let mut bst = Self::new();
+        let mut bst = Self::default();
 
         for (k, v) in array {
             bst.insert(k, v);
@@ -348,7 +494,7 @@ impl<K: Ord, V, const N: usize> From<[(K, V); N]> for BSTMap<K, V> {
     }
 }
 
-impl<K: Ord, V> Extend<(K, V)> for BSTMap<K, V> {
+impl<K, V, C: Comparator<K>> Extend<(K, V)> for BSTMap<K, V, C> {
     fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
         for (k, v) in iter {
             self.insert(k, v);
@@ -360,9 +506,11 @@ impl<K: Ord, V> Extend<(K, V)> for BSTMap<K, V> {
 mod tests {
     use crate::iter::{
         BSTMapByrefBreadthfirstIter, BSTMapByrefInorderIter, BSTMapByrefInorderIterMut,
-        BSTMapConsumingInorderIter,
+        BSTMapByrefRangeIter, BSTMapConsumingInorderIter,
     };
 
+    use std::ops::Bound;
+
     use super::BSTMap;
 
     // TODO: maybe deduplicate code for various iterators?
@@ -651,4 +799,65 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn byref_range_iter_is_empty_from_empty_map() {
+        let bst = BSTMap::<u32, String>::new();
+
+        let mut iter = BSTMapByrefRangeIter::new(&bst, ..);
+        let next_item = iter.next();
+
+        assert!(next_item.is_none());
+    }
+
+    #[test]
+    fn byref_range_iter_respects_bounds() {
+        let mut bst = BSTMap::<u32, String>::new();
+
+        const SERIES_OF_INSERTIONS: [(u32, &str); 8] = [
+            (13, "hello"),
+            (15, "bye"),
+            (7, "test"),
+            (2, "test2"),
+            (8, "high number"),
+            (14, "fourteen"),
+            (20, "twenty"),
+            (1, "one"),
+        ];
+
+        for (k, v) in &SERIES_OF_INSERTIONS {
+            bst.insert(*k, v.to_string());
+        }
+
+        let keys = |iter: BSTMapByrefRangeIter<'_, u32, String>| -> Vec<u32> {
+            iter.map(|(k, _)| *k).collect()
+        };
+
+        assert_eq!(
+            keys(BSTMapByrefRangeIter::new(&bst, ..)),
+            vec![1, 2, 7, 8, 13, 14, 15, 20]
+        );
+        assert_eq!(
+            keys(BSTMapByrefRangeIter::new(&bst, 7..15)),
+            vec![7, 8, 13, 14]
+        );
+        assert_eq!(
+            keys(BSTMapByrefRangeIter::new(&bst, 3..=15)),
+            vec![7, 8, 13, 14, 15]
+        );
+        assert_eq!(
+            keys(BSTMapByrefRangeIter::new(&bst, 14..)),
+            vec![14, 15, 20]
+        );
+        assert_eq!(keys(BSTMapByrefRangeIter::new(&bst, ..2)), vec![1]);
+        assert!(keys(BSTMapByrefRangeIter::new(&bst, 9..13)).is_empty());
+        assert!(keys(BSTMapByrefRangeIter::new(&bst, 21..)).is_empty());
+        assert!(
+            keys(BSTMapByrefRangeIter::new(
+                &bst,
+                (Bound::Excluded(15), Bound::Included(7))
+            ))
+            .is_empty()
+        );
+    }
 }
@@ -15,18 +15,85 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::{
+    borrow::Borrow,
     cmp::{Ord, Ordering},
     collections::VecDeque,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
     mem,
+    ops::{Index, IndexMut, RangeBounds},
 };
 
 mod iter;
 use crate::iter::{
     BSTMapByrefBreadthfirstIter, BSTMapByrefInorderIter, BSTMapByrefInorderIterMut,
-    BSTMapConsumingInorderIter,
+    BSTMapByrefRangeIter, BSTMapConsumingInorderIter, BSTMapIntoKeysIter, BSTMapIntoValuesIter,
+    BSTMapKeysIter, BSTMapValuesIter, BSTMapValuesMutIter,
 };
 
 mod debug;
+pub use crate::debug::{DotOptions, PrettyLayout};
+
+mod comparator;
+pub use crate::comparator::{Comparator, OrdComparator, ReverseComparator};
+
+mod codec;
+pub use crate::codec::{Decode, DecodeRef, Encode};
+
+mod batch;
+pub use crate::batch::{Transaction, WriteBatch};
+
+mod wal;
+pub use crate::wal::FsyncPolicy;
+
+mod snapshot;
+
+mod durable;
+pub use crate::durable::{DurableBSTMap, DurableTransaction};
+
+mod expiring;
+pub use crate::expiring::{Clock, ExpiringBSTMap, ExpiringBSTMapIter, ManualClock, SystemClock};
+
+mod image;
+#[cfg(feature = "mmap")]
+pub use crate::image::BSTMapImageFile;
+pub use crate::image::{BSTMapImage, BSTMapImageIter};
+
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "rayon")]
+pub use crate::par::{BSTMapParIter, BSTMapParIterMut};
+
+mod arena;
+pub use crate::arena::{
+    ArenaBSTMap, ArenaBSTMapBreadthfirstIter, ArenaBSTMapConsumingInorderIter,
+    ArenaBSTMapInorderIter, ArenaBSTMapInorderIterMut,
+};
+
+mod persistent;
+pub use crate::persistent::{PersistentBSTMap, PersistentBSTMapIntoIter, PersistentBSTMapIter};
+
+mod sharded;
+pub use crate::sharded::{ShardedBSTMap, ShardedBSTMapIter};
+
+mod versioned;
+pub use crate::versioned::{BSTMapSnapshot, VersionedBSTMap};
+
+mod multimap;
+pub use crate::multimap::{BSTMultiMap, BSTMultiMapGroupsIter, BSTMultiMapIter};
+
+mod multiset;
+pub use crate::multiset::{BSTMultiSet, BSTMultiSetCountsIter, BSTMultiSetIter};
+
+mod validate;
+pub use crate::validate::{Direction, ValidationError, Violation};
+
+mod stats;
+pub use crate::stats::TreeStats;
+
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
 
 // Shorthand for a referece to a Box'ed node that may or may not be there
 type NodeRef<K, V> = Option<Box<Node<K, V>>>;
@@ -38,7 +105,7 @@ struct Node<K, V> {
     value: V,
 }
 
-impl<K: Ord, V> Node<K, V> {
+impl<K, V> Node<K, V> {
     pub fn new(key: K, value: V) -> Self {
         Self {
             left: None,
@@ -49,19 +116,32 @@ impl<K: Ord, V> Node<K, V> {
     }
 }
 
-pub struct BSTMap<K: Ord, V> {
+// Keys are ordered by comparator C, which by default uses Ord
+pub struct BSTMap<K, V, C = OrdComparator> {
     head: NodeRef<K, V>,
     length: usize,
+    comparator: C,
 }
 
 impl<K: Ord, V> BSTMap<K, V> {
     pub fn new() -> Self {
+        Self::with_comparator(OrdComparator)
+    }
+}
+
+impl<K, V, C: Comparator<K>> BSTMap<K, V, C> {
+    pub fn with_comparator(comparator: C) -> Self {
         Self {
             head: None,
             length: 0,
+            comparator,
         }
     }
 
+    pub fn comparator(&self) -> &C {
+        &self.comparator
+    }
+
     pub fn len(&self) -> usize {
         self.length
     }
@@ -82,7 +162,7 @@ impl<K: Ord, V> BSTMap<K, V> {
             // unwrap is safe inside the loop, since current_node is Some
             let inner = current_node.as_mut().unwrap();
 
-            current_node = match inner.key.cmp(&key) {
+            current_node = match self.comparator.compare(&inner.key, &key) {
                 Ordering::Less => &mut inner.right,
                 Ordering::Greater => &mut inner.left,
                 Ordering::Equal => {
@@ -105,7 +185,7 @@ impl<K: Ord, V> BSTMap<K, V> {
 
         while let Some(inner) = current_node.as_ref() {
             // unwrap is safe inside the loop, since current_node is Some
-            current_node = match inner.key.cmp(&key) {
+            current_node = match self.comparator.compare(&inner.key, &key) {
                 Ordering::Less => &inner.right,
                 Ordering::Greater => &inner.left,
                 Ordering::Equal => return true,
@@ -119,7 +199,7 @@ impl<K: Ord, V> BSTMap<K, V> {
         let mut current_node = &self.head;
 
         while let Some(inner) = current_node.as_ref() {
-            current_node = match inner.key.cmp(key) {
+            current_node = match self.comparator.compare(&inner.key, key) {
                 Ordering::Less => &inner.right,
                 Ordering::Greater => &inner.left,
                 Ordering::Equal => return Some(&inner.value),
@@ -133,7 +213,7 @@ impl<K: Ord, V> BSTMap<K, V> {
         let mut current_node = &mut self.head;
 
         while let Some(inner) = current_node.as_mut() {
-            current_node = match inner.key.cmp(key) {
+            current_node = match self.comparator.compare(&inner.key, key) {
                 Ordering::Less => &mut inner.right,
                 Ordering::Greater => &mut inner.left,
                 Ordering::Equal => return Some(&mut inner.value),
@@ -143,6 +223,66 @@ impl<K: Ord, V> BSTMap<K, V> {
         None
     }
 
+    // Entry with the smallest key
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let mut current_node = self.head.as_ref()?;
+
+        while let Some(left_node) = current_node.left.as_ref() {
+            current_node = left_node;
+        }
+
+        Some((&current_node.key, &current_node.value))
+    }
+
+    // Entry with the largest key
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut current_node = self.head.as_ref()?;
+
+        while let Some(right_node) = current_node.right.as_ref() {
+            current_node = right_node;
+        }
+
+        Some((&current_node.key, &current_node.value))
+    }
+
+    // Removes and returns the entry with the smallest key
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let mut current_node = &mut self.head;
+
+        while current_node.as_ref()?.left.is_some() {
+            // unwrap is safe, checked in the loop condition
+            current_node = &mut current_node.as_mut().unwrap().left;
+        }
+
+        // Leftmost node has no left child, so it is replaced by its right subtree
+        let Node {
+            right, key, value, ..
+        } = *current_node.take()?;
+        *current_node = right;
+        self.length -= 1;
+
+        Some((key, value))
+    }
+
+    // Removes and returns the entry with the largest key
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let mut current_node = &mut self.head;
+
+        while current_node.as_ref()?.right.is_some() {
+            // unwrap is safe, checked in the loop condition
+            current_node = &mut current_node.as_mut().unwrap().right;
+        }
+
+        // Rightmost node has no right child, so it is replaced by its left subtree
+        let Node {
+            left, key, value, ..
+        } = *current_node.take()?;
+        *current_node = left;
+        self.length -= 1;
+
+        Some((key, value))
+    }
+
     pub fn remove(&mut self, key: K) -> Option<V> {
         // First - find current node, if it is even in there
         let mut current_node = &mut self.head;
@@ -153,7 +293,10 @@ impl<K: Ord, V> BSTMap<K, V> {
             };
 
             // current_node is Some, so unwrap is safe
-            current_node = match current_node.as_ref().unwrap().key.cmp(&key) {
+            current_node = match self
+                .comparator
+                .compare(&current_node.as_ref().unwrap().key, &key)
+            {
                 Ordering::Less => &mut current_node.as_mut().unwrap().right,
                 Ordering::Greater => &mut current_node.as_mut().unwrap().left,
                 Ordering::Equal => break current_node,
@@ -269,6 +412,10 @@ impl<K: Ord, V> BSTMap<K, V> {
         BSTMapByrefBreadthfirstIter::new(self)
     }
 
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> BSTMapByrefRangeIter<'_, K, V> {
+        BSTMapByrefRangeIter::new(self, range)
+    }
+
     pub fn iter(&self) -> BSTMapByrefInorderIter<'_, K, V> {
         self.iter_inorder()
     }
@@ -276,23 +423,169 @@ impl<K: Ord, V> BSTMap<K, V> {
     pub fn iter_mut(&mut self) -> BSTMapByrefInorderIterMut<'_, K, V> {
         self.iter_inorder_mut()
     }
+
+    // Keys in order
+    pub fn keys(&self) -> BSTMapKeysIter<'_, K, V> {
+        BSTMapKeysIter {
+            inner: self.iter_inorder(),
+        }
+    }
+
+    // Values in order of their keys
+    pub fn values(&self) -> BSTMapValuesIter<'_, K, V> {
+        BSTMapValuesIter {
+            inner: self.iter_inorder(),
+        }
+    }
+
+    pub fn values_mut(&mut self) -> BSTMapValuesMutIter<'_, K, V> {
+        BSTMapValuesMutIter {
+            inner: self.iter_inorder_mut(),
+        }
+    }
+
+    pub fn into_keys(self) -> BSTMapIntoKeysIter<K, V> {
+        BSTMapIntoKeysIter {
+            inner: self.into_iter_inorder(),
+        }
+    }
+
+    pub fn into_values(self) -> BSTMapIntoValuesIter<K, V> {
+        BSTMapIntoValuesIter {
+            inner: self.into_iter_inorder(),
+        }
+    }
 }
 
-impl<K: Ord, V> Default for BSTMap<K, V> {
+impl<K, V, C: Comparator<K> + Default> Default for BSTMap<K, V, C> {
     fn default() -> Self {
-        Self::new()
+        Self::with_comparator(C::default())
+    }
+}
+
+// map[&key] looks up keys of any type K borrows as (e.g. &str for String keys),
+// as long as the comparator can compare it - OrdComparator can compare any Ord type.
+// Same as with BTreeMap, Borrow has to keep the order of keys
+impl<K: Borrow<Q>, V, C: Comparator<Q>, Q: ?Sized> Index<&Q> for BSTMap<K, V, C> {
+    type Output = V;
+
+    // Panics if there is no entry for key
+    fn index(&self, key: &Q) -> &V {
+        let mut current_node = &self.head;
+
+        while let Some(inner) = current_node.as_ref() {
+            current_node = match self.comparator.compare(inner.key.borrow(), key) {
+                Ordering::Less => &inner.right,
+                Ordering::Greater => &inner.left,
+                Ordering::Equal => return &inner.value,
+            }
+        }
+
+        panic!("no entry found for key in BSTMap");
+    }
+}
+
+// Only changes values of existing entries, map[&key] = value does not insert missing keys
+impl<K: Borrow<Q>, V, C: Comparator<Q>, Q: ?Sized> IndexMut<&Q> for BSTMap<K, V, C> {
+    // Panics if there is no entry for key
+    fn index_mut(&mut self, key: &Q) -> &mut V {
+        let mut current_node = &mut self.head;
+
+        while let Some(inner) = current_node.as_mut() {
+            current_node = match self.comparator.compare(inner.key.borrow(), key) {
+                Ordering::Less => &mut inner.right,
+                Ordering::Greater => &mut inner.left,
+                Ordering::Equal => return &mut inner.value,
+            }
+        }
+
+        panic!("no entry found for key in BSTMap");
+    }
+}
+
+// Same as with drop, derived Clone would be recursive, so clone nodes iteratively
+// The copy has the same shape as the original
+impl<K: Clone, V: Clone, C: Clone> Clone for BSTMap<K, V, C> {
+    fn clone(&self) -> Self {
+        let mut head = None;
+        // Nodes of the original along with the empty slots their copies go to
+        let mut stack = vec![];
+
+        if let Some(node) = self.head.as_deref() {
+            stack.push((node, &mut head));
+        }
+
+        while let Some((node, slot)) = stack.pop() {
+            let copy = slot.insert(Box::new(Node::new(node.key.clone(), node.value.clone())));
+
+            if let Some(left) = node.left.as_deref() {
+                stack.push((left, &mut copy.left));
+            }
+
+            if let Some(right) = node.right.as_deref() {
+                stack.push((right, &mut copy.right));
+            }
+        }
+
+        Self {
+            head,
+            length: self.length,
+            comparator: self.comparator.clone(),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, C> Debug for BSTMap<K, V, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(BSTMapByrefInorderIter::new(self))
+            .finish()
+    }
+}
+
+// Maps are compared by their entries in key order, the shape of the tree does not matter
+impl<K: PartialEq, V: PartialEq, C> PartialEq for BSTMap<K, V, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.length == other.length
+            && BSTMapByrefInorderIter::new(self).eq(BSTMapByrefInorderIter::new(other))
+    }
+}
+
+impl<K: Eq, V: Eq, C> Eq for BSTMap<K, V, C> {}
+
+// Lexicographical comparison of entries in key order, same as BTreeMap
+impl<K: PartialOrd, V: PartialOrd, C> PartialOrd for BSTMap<K, V, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        BSTMapByrefInorderIter::new(self).partial_cmp(BSTMapByrefInorderIter::new(other))
+    }
+}
+
+impl<K: Ord, V: Ord, C> Ord for BSTMap<K, V, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        BSTMapByrefInorderIter::new(self).cmp(BSTMapByrefInorderIter::new(other))
+    }
+}
+
+// Consistent with PartialEq - hashes the entries in key order, prefixed with their number
+impl<K: Hash, V: Hash, C> Hash for BSTMap<K, V, C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.length);
+
+        for entry in BSTMapByrefInorderIter::new(self) {
+            entry.hash(state);
+        }
     }
 }
 
 // In order to avoid recursive calls to drop
 // provide an iterative version
-impl<K: Ord, V> Drop for BSTMap<K, V> {
+impl<K, V, C> Drop for BSTMap<K, V, C> {
     fn drop(&mut self) {
         if self.head.is_none() {
             return;
         };
 
-        let mut queue = VecDeque::<Box<Node<K, V>>>::with_capacity(self.len());
+        let mut queue = VecDeque::<Box<Node<K, V>>>::with_capacity(self.length);
 
         queue.push_front(self.head.take().unwrap());
 
@@ -312,7 +605,9 @@ impl<K: Ord, V> Drop for BSTMap<K, V> {
 
 #[cfg(test)]
 mod tests {
-    use super::BSTMap;
+    use std::{cmp::Ordering, collections::HashSet, ops::Bound};
+
+    use super::{BSTMap, Comparator, Node, ReverseComparator};
 
     #[test]
     fn new_map_is_empty() {
@@ -605,4 +900,211 @@ mod tests {
             assert_eq!(*child_node.unwrap(), v.to_string());
         }
     }
+
+    #[test]
+    fn first_and_last_key_value() {
+        let mut bst = BSTMap::<u32, String>::new();
+
+        assert!(bst.first_key_value().is_none());
+        assert!(bst.last_key_value().is_none());
+
+        const SERIES_OF_INSERTIONS: [(u32, &str); 5] = [
+            (12, "hello"),
+            (35, "bye"),
+            (999, "test"),
+            (0, "test2"),
+            (36, "utf8 string ąąąąą"),
+        ];
+        for (k, v) in &SERIES_OF_INSERTIONS {
+            bst.insert(*k, v.to_string());
+        }
+
+        assert_eq!(bst.first_key_value(), Some((&0, &"test2".to_string())));
+        assert_eq!(bst.last_key_value(), Some((&999, &"test".to_string())));
+    }
+
+    #[test]
+    fn range_returns_sorted_entries_within_bounds() {
+        let bst: BSTMap<u32, u32> = [(10, 1), (5, 2), (15, 3), (2, 4), (13, 5), (20, 6)].into();
+
+        let collected: Vec<_> = bst.range(5..=15).map(|(k, v)| (*k, *v)).collect();
+
+        assert_eq!(collected, vec![(5, 2), (10, 1), (13, 5), (15, 3)]);
+    }
+
+    #[test]
+    fn pop_first_and_last_return_entries_in_order() {
+        let mut bst: BSTMap<u32, u32> =
+            [(10, 1), (5, 2), (15, 3), (2, 4), (7, 5), (13, 6), (20, 7)].into();
+
+        assert_eq!(bst.pop_first(), Some((2, 4)));
+        assert_eq!(bst.pop_last(), Some((20, 7)));
+        assert_eq!(bst.pop_first(), Some((5, 2)));
+        assert_eq!(bst.pop_last(), Some((15, 3)));
+        assert_eq!(bst.len(), 3);
+
+        let remaining: Vec<_> = bst.iter().map(|(k, _)| *k).collect();
+        assert_eq!(remaining, vec![7, 10, 13]);
+
+        bst.clear();
+        assert!(bst.pop_first().is_none());
+        assert!(bst.pop_last().is_none());
+    }
+
+    #[test]
+    fn comparator_decides_order_of_keys() {
+        // Keys which differ only in case are the same key
+        struct CaseInsensitive;
+
+        impl Comparator<String> for CaseInsensitive {
+            fn compare(&self, a: &String, b: &String) -> Ordering {
+                a.to_lowercase().cmp(&b.to_lowercase())
+            }
+
+            fn name(&self) -> &str {
+                "case-insensitive"
+            }
+        }
+
+        let mut bst = BSTMap::with_comparator(CaseInsensitive);
+        bst.insert("b".to_string(), 1);
+        bst.insert("A".to_string(), 2);
+        bst.insert("C".to_string(), 3);
+
+        assert_eq!(bst.insert("a".to_string(), 4), Some(2));
+        assert_eq!(bst.get(&"c".to_string()), Some(&3));
+        assert_eq!(bst.remove("B".to_string()), Some(1));
+        assert_eq!(
+            bst.iter()
+                .map(|(k, v)| (k.as_str(), *v))
+                .collect::<Vec<_>>(),
+            vec![("A", 4), ("C", 3)]
+        );
+
+        let reversed: BSTMap<u32, (), ReverseComparator> =
+            [(1, ()), (5, ()), (3, ()), (4, ())].into();
+
+        assert_eq!(
+            reversed.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![5, 4, 3, 1]
+        );
+        assert_eq!(
+            reversed
+                .range((Bound::Included(4), Bound::Included(1)))
+                .map(|(k, _)| *k)
+                .collect::<Vec<_>>(),
+            vec![4, 3, 1]
+        );
+        assert_eq!(reversed.first_key_value(), Some((&5, &())));
+    }
+
+    #[test]
+    fn equality_ignores_shape_of_the_tree() {
+        let a: BSTMap<u32, char> = [(2, 'b'), (1, 'a'), (3, 'c')].into();
+        let b: BSTMap<u32, char> = [(1, 'a'), (2, 'b'), (3, 'c')].into();
+        let c: BSTMap<u32, char> = [(1, 'a'), (2, 'b'), (3, 'x')].into();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a < c);
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        assert!(BSTMap::<u32, char>::new() < a);
+
+        // Equal maps hash the same, so they can be put in hashed collections
+        let set: HashSet<_> = [a, b, c].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn debug_prints_entries_in_key_order() {
+        let bst: BSTMap<u32, &str> = [(2, "two"), (1, "one")].into();
+
+        assert_eq!(format!("{bst:?}"), r#"{1: "one", 2: "two"}"#);
+    }
+
+    #[test]
+    fn clone_keeps_shape_and_handles_degenerate_trees() {
+        let bst: BSTMap<u32, u32> = [(2, 20), (1, 10), (3, 30)].into();
+        let mut copy = bst.clone();
+
+        assert_eq!(copy, bst);
+        assert_eq!(
+            copy.pretty_debug(Default::default()),
+            bst.pretty_debug(Default::default())
+        );
+
+        copy.insert(4, 40);
+        assert_eq!(bst.len(), 3);
+
+        // A list deep enough to overflow the stack with recursive clone,
+        // built by hand since inserting sorted keys one by one takes quadratic time
+        let mut degenerate = BSTMap::<u32, ()>::new();
+        let mut slot = &mut degenerate.head;
+        for key in 0..200_000 {
+            slot = &mut slot.insert(Box::new(Node::new(key, ()))).right;
+        }
+        degenerate.length = 200_000;
+
+        let copy = degenerate.clone();
+
+        assert_eq!(copy.len(), 200_000);
+        assert_eq!(copy.stats().height, 200_000);
+        assert_eq!(copy.validate(), Ok(()));
+    }
+
+    #[test]
+    fn index_with_borrowed_keys() {
+        let mut bst: BSTMap<String, u32> = [("b".to_string(), 2), ("a".to_string(), 1)].into();
+
+        assert_eq!(bst["a"], 1);
+        assert_eq!(bst[&"b".to_string()], 2);
+
+        bst["a"] = 10;
+        bst["b"] += 5;
+
+        assert_eq!(bst.get(&"a".to_string()), Some(&10));
+        assert_eq!(bst["b"], 7);
+        assert_eq!(bst.len(), 2);
+
+        let reversed: BSTMap<u32, &str, ReverseComparator> = [(1, "one"), (2, "two")].into();
+        assert_eq!(reversed[&2], "two");
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry found for key in BSTMap")]
+    fn index_of_missing_key_panics() {
+        let bst: BSTMap<u32, u32> = [(1, 1)].into();
+        let _ = bst[&2];
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry found for key in BSTMap")]
+    fn index_mut_does_not_insert_missing_key() {
+        let mut bst: BSTMap<u32, u32> = [(1, 1)].into();
+        bst[&2] = 2;
+    }
+
+    #[test]
+    fn keys_and_values_iterators() {
+        let mut bst: BSTMap<u32, String> = [
+            (2, "b".to_string()),
+            (3, "c".to_string()),
+            (1, "a".to_string()),
+        ]
+        .into();
+
+        assert_eq!(bst.keys().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(bst.values().cloned().collect::<String>(), "abc");
+
+        for value in bst.values_mut() {
+            value.make_ascii_uppercase();
+        }
+
+        let mut keys = bst.keys();
+        keys.by_ref().for_each(drop);
+        assert_eq!(keys.next(), None);
+
+        assert_eq!(bst.clone().into_keys().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(bst.into_values().collect::<Vec<_>>(), vec!["A", "B", "C"]);
+    }
 }
@@ -0,0 +1,295 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains BSTMap variant which keeps every value inserted under a key
+//
+// Each key has a single node holding all of its values in insertion order,
+// so duplicates do not make the tree any deeper. Values are kept in a VecDeque,
+// so removing the oldest one does not shift all the others
+
+use std::{
+    collections::{VecDeque, vec_deque},
+    iter::FusedIterator,
+};
+
+use crate::{BSTMap, Comparator, OrdComparator, iter::BSTMapByrefInorderIter};
+
+pub struct BSTMultiMap<K, V, C = OrdComparator> {
+    // never holds empty VecDeques, keys without values are removed
+    bst: BSTMap<K, VecDeque<V>, C>,
+    length: usize,
+}
+
+impl<K: Ord, V> BSTMultiMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_comparator(OrdComparator)
+    }
+}
+
+impl<K, V, C: Comparator<K>> BSTMultiMap<K, V, C> {
+    pub fn with_comparator(comparator: C) -> Self {
+        Self {
+            bst: BSTMap::with_comparator(comparator),
+            length: 0,
+        }
+    }
+
+    // Number of values, counting every value of every key
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    // Number of distinct keys
+    pub fn key_count(&self) -> usize {
+        self.bst.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.bst.clear();
+        self.length = 0;
+    }
+
+    // Adds value after all values already stored under key
+    pub fn insert(&mut self, key: K, value: V) {
+        match self.bst.get_mut(&key) {
+            Some(values) => values.push_back(value),
+            None => {
+                self.bst.insert(key, VecDeque::from([value]));
+            }
+        }
+
+        self.length += 1;
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.bst.get(key).is_some()
+    }
+
+    // Number of values stored under key
+    pub fn count(&self, key: &K) -> usize {
+        self.bst.get(key).map_or(0, VecDeque::len)
+    }
+
+    // All values stored under key in insertion order, empty if there are none
+    pub fn get_all(&self, key: &K) -> vec_deque::Iter<'_, V> {
+        self.bst.get(key).map(VecDeque::iter).unwrap_or_default()
+    }
+
+    // Oldest value stored under key
+    pub fn get_first(&self, key: &K) -> Option<&V> {
+        self.bst.get(key)?.front()
+    }
+
+    // Removes and returns the oldest value stored under key
+    pub fn remove_one(&mut self, key: K) -> Option<V> {
+        let values = self.bst.get_mut(&key)?;
+        // unwrap is safe, there are no empty VecDeques
+        let value = values.pop_front().unwrap();
+
+        if values.is_empty() {
+            self.bst.remove(key);
+        }
+
+        self.length -= 1;
+        Some(value)
+    }
+
+    // Removes and returns all values stored under key in insertion order
+    pub fn remove_all(&mut self, key: K) -> Vec<V> {
+        let values = self.bst.remove(key).unwrap_or_default();
+        self.length -= values.len();
+        values.into()
+    }
+
+    // In-order iteration over (key, value) pairs, values of a key in insertion order
+    pub fn iter(&self) -> BSTMultiMapIter<'_, K, V> {
+        BSTMultiMapIter {
+            groups: self.bst.iter(),
+            current: None,
+            remaining: self.length,
+        }
+    }
+
+    // In-order iteration over keys along with all of their values
+    pub fn iter_groups(&self) -> BSTMultiMapGroupsIter<'_, K, V> {
+        BSTMultiMapGroupsIter {
+            groups: self.bst.iter(),
+        }
+    }
+}
+
+impl<K, V, C: Comparator<K> + Default> Default for BSTMultiMap<K, V, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+// Iterator over (key, value) pairs of BSTMultiMap
+pub struct BSTMultiMapIter<'a, K, V> {
+    groups: BSTMapByrefInorderIter<'a, K, VecDeque<V>>,
+    // key of the group being iterated over, along with its values not returned yet
+    current: Option<(&'a K, vec_deque::Iter<'a, V>)>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for BSTMultiMapIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, values)) = &mut self.current
+                && let Some(value) = values.next()
+            {
+                self.remaining -= 1;
+                return Some((*key, value));
+            }
+
+            let (key, values) = self.groups.next()?;
+            self.current = Some((key, values.iter()));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for BSTMultiMapIter<'_, K, V> {}
+
+impl<K, V> FusedIterator for BSTMultiMapIter<'_, K, V> {}
+
+// Iterator over keys of BSTMultiMap along with all of their values
+pub struct BSTMultiMapGroupsIter<'a, K, V> {
+    groups: BSTMapByrefInorderIter<'a, K, VecDeque<V>>,
+}
+
+impl<'a, K, V> Iterator for BSTMultiMapGroupsIter<'a, K, V> {
+    type Item = (&'a K, vec_deque::Iter<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.groups.next().map(|(key, values)| (key, values.iter()))
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>> IntoIterator for &'a BSTMultiMap<K, V, C> {
+    type Item = (&'a K, &'a V);
+
+    type IntoIter = BSTMultiMapIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, C: Comparator<K> + Default> FromIterator<(K, V)> for BSTMultiMap<K, V, C> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut multimap = Self::default();
+        multimap.extend(iter);
+        multimap
+    }
+}
+
+impl<K, V, C: Comparator<K>> Extend<(K, V)> for BSTMultiMap<K, V, C> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BSTMultiMap;
+
+    fn sample() -> BSTMultiMap<&'static str, u32> {
+        [("b", 1), ("a", 2), ("b", 3), ("c", 4), ("b", 5), ("a", 6)]
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn values_are_kept_in_insertion_order() {
+        let multimap = sample();
+
+        assert_eq!(multimap.len(), 6);
+        assert_eq!(multimap.key_count(), 3);
+        assert!(multimap.get_all(&"b").eq(&[1, 3, 5]));
+        assert_eq!(multimap.get_first(&"a"), Some(&2));
+        assert_eq!(multimap.count(&"c"), 1);
+        assert_eq!(multimap.count(&"d"), 0);
+        assert_eq!(multimap.get_all(&"d").len(), 0);
+    }
+
+    #[test]
+    fn removal_of_one_and_all_values() {
+        let mut multimap = sample();
+
+        assert_eq!(multimap.remove_one("b"), Some(1));
+        assert!(multimap.get_all(&"b").eq(&[3, 5]));
+        assert_eq!(multimap.remove_one("c"), Some(4));
+        assert!(!multimap.contains(&"c"));
+        assert_eq!(multimap.remove_one("c"), None);
+
+        assert_eq!(multimap.remove_all("a"), vec![2, 6]);
+        assert!(multimap.remove_all("a").is_empty());
+        assert_eq!(multimap.len(), 2);
+        assert_eq!(multimap.key_count(), 1);
+    }
+
+    #[test]
+    fn values_can_be_used_as_a_queue() {
+        let mut multimap = BSTMultiMap::new();
+
+        // Removing from the front and adding to the back wraps the values around their buffer
+        for round in 0..100u32 {
+            for value in round * 4..round * 4 + 4 {
+                multimap.insert("q", value);
+            }
+            for value in round * 2..round * 2 + 2 {
+                assert_eq!(multimap.remove_one("q"), Some(value));
+            }
+        }
+
+        assert_eq!(multimap.len(), 200);
+        assert_eq!(multimap.get_first(&"q"), Some(&200));
+        assert!(multimap.get_all(&"q").copied().eq(200..400));
+    }
+
+    #[test]
+    fn iteration_over_pairs_and_groups() {
+        let multimap = sample();
+
+        let pairs: Vec<_> = multimap.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(
+            pairs,
+            vec![("a", 2), ("a", 6), ("b", 1), ("b", 3), ("b", 5), ("c", 4)]
+        );
+        assert_eq!(multimap.iter().len(), 6);
+
+        let groups: Vec<_> = multimap
+            .iter_groups()
+            .map(|(k, values)| (*k, values.copied().collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![("a", vec![2, 6]), ("b", vec![1, 3, 5]), ("c", vec![4])]
+        );
+    }
+}
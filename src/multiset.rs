@@ -0,0 +1,290 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains a multiset (bag) - a set counting how many times each element was inserted
+//
+// Every distinct element has a single node holding its count, counts are never zero
+
+use std::iter::FusedIterator;
+
+use crate::{BSTMap, Comparator, OrdComparator, iter::BSTMapByrefInorderIter};
+
+pub struct BSTMultiSet<T, C = OrdComparator> {
+    bst: BSTMap<T, usize, C>,
+    // sum of all counts
+    total_length: usize,
+}
+
+impl<T: Ord> BSTMultiSet<T> {
+    pub fn new() -> Self {
+        Self::with_comparator(OrdComparator)
+    }
+}
+
+impl<T, C: Comparator<T>> BSTMultiSet<T, C> {
+    pub fn with_comparator(comparator: C) -> Self {
+        Self {
+            bst: BSTMap::with_comparator(comparator),
+            total_length: 0,
+        }
+    }
+
+    // Number of distinct elements
+    pub fn len(&self) -> usize {
+        self.bst.len()
+    }
+
+    // Number of elements, counting every repetition
+    pub fn total_len(&self) -> usize {
+        self.total_length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_length == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.bst.clear();
+        self.total_length = 0;
+    }
+
+    // Returns count of value after the insertion
+    pub fn insert(&mut self, value: T) -> usize {
+        self.insert_n(value, 1)
+    }
+
+    // Inserts n copies of value, returns its count after the insertion
+    pub fn insert_n(&mut self, value: T, n: usize) -> usize {
+        if n == 0 {
+            return self.count(&value);
+        }
+
+        self.total_length += n;
+
+        match self.bst.get_mut(&value) {
+            Some(count) => {
+                *count += n;
+                *count
+            }
+            None => {
+                self.bst.insert(value, n);
+                n
+            }
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.bst.get(value).is_some()
+    }
+
+    // How many times value is in the set, 0 if it is not there
+    pub fn count(&self, value: &T) -> usize {
+        self.bst.get(value).copied().unwrap_or(0)
+    }
+
+    // Removes a single copy of value, returns whether there was one
+    pub fn remove_one(&mut self, value: T) -> bool {
+        let Some(count) = self.bst.get_mut(&value) else {
+            return false;
+        };
+
+        *count -= 1;
+        if *count == 0 {
+            self.bst.remove(value);
+        }
+
+        self.total_length -= 1;
+        true
+    }
+
+    // Removes all copies of value, returns how many there were
+    pub fn remove_all(&mut self, value: T) -> usize {
+        let count = self.bst.remove(value).unwrap_or(0);
+        self.total_length -= count;
+        count
+    }
+
+    // Element at given index of the sorted sequence of all elements (each repeated by its count),
+    // e.g. select(total_len() / 2) is the median
+    // Nodes do not keep sizes of their subtrees, so this walks the elements in order - O(n)
+    pub fn select(&self, mut index: usize) -> Option<&T> {
+        for (value, &count) in self.bst.iter() {
+            if index < count {
+                return Some(value);
+            }
+            index -= count;
+        }
+
+        None
+    }
+
+    // In-order iteration, each element repeated by its count
+    pub fn iter(&self) -> BSTMultiSetIter<'_, T> {
+        BSTMultiSetIter {
+            counts: self.bst.iter(),
+            current: None,
+            remaining: self.total_length,
+        }
+    }
+
+    // In-order iteration over distinct elements along with their counts
+    pub fn iter_counts(&self) -> BSTMultiSetCountsIter<'_, T> {
+        BSTMultiSetCountsIter {
+            counts: self.bst.iter(),
+        }
+    }
+}
+
+impl<T, C: Comparator<T> + Default> Default for BSTMultiSet<T, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+// Iterator over elements of BSTMultiSet, repeating each one by its count
+pub struct BSTMultiSetIter<'a, T> {
+    counts: BSTMapByrefInorderIter<'a, T, usize>,
+    // element being repeated, along with the number of repetitions left
+    current: Option<(&'a T, usize)>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for BSTMultiSetIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((value, left)) = &mut self.current
+                && *left > 0
+            {
+                *left -= 1;
+                self.remaining -= 1;
+                return Some(*value);
+            }
+
+            let (value, count) = self.counts.next()?;
+            self.current = Some((value, *count));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for BSTMultiSetIter<'_, T> {}
+
+impl<T> FusedIterator for BSTMultiSetIter<'_, T> {}
+
+// Iterator over distinct elements of BSTMultiSet along with their counts
+pub struct BSTMultiSetCountsIter<'a, T> {
+    counts: BSTMapByrefInorderIter<'a, T, usize>,
+}
+
+impl<'a, T> Iterator for BSTMultiSetCountsIter<'a, T> {
+    type Item = (&'a T, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.counts.next().map(|(value, count)| (value, *count))
+    }
+}
+
+impl<'a, T, C: Comparator<T>> IntoIterator for &'a BSTMultiSet<T, C> {
+    type Item = &'a T;
+
+    type IntoIter = BSTMultiSetIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, C: Comparator<T> + Default> FromIterator<T> for BSTMultiSet<T, C> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut multiset = Self::default();
+        multiset.extend(iter);
+        multiset
+    }
+}
+
+impl<T, C: Comparator<T>> Extend<T> for BSTMultiSet<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BSTMultiSet;
+
+    fn sample() -> BSTMultiSet<char> {
+        "abracadabra".chars().collect()
+    }
+
+    #[test]
+    fn counts_of_elements() {
+        let mut multiset = sample();
+
+        assert_eq!(multiset.len(), 5);
+        assert_eq!(multiset.total_len(), 11);
+        assert_eq!(multiset.count(&'a'), 5);
+        assert_eq!(multiset.count(&'z'), 0);
+
+        assert_eq!(multiset.insert_n('z', 3), 3);
+        assert_eq!(multiset.insert('a'), 6);
+        assert_eq!(multiset.insert_n('y', 0), 0);
+        assert!(!multiset.contains(&'y'));
+        assert_eq!(multiset.total_len(), 15);
+
+        assert!(multiset.remove_one('c'));
+        assert!(!multiset.contains(&'c'));
+        assert!(!multiset.remove_one('c'));
+        assert_eq!(multiset.remove_all('a'), 6);
+        assert_eq!(multiset.len(), 4);
+        assert_eq!(multiset.total_len(), 8);
+    }
+
+    #[test]
+    fn iteration_repeats_elements_by_count() {
+        let multiset = sample();
+
+        assert_eq!(multiset.iter().collect::<String>(), "aaaaabbcdrr");
+        assert_eq!(multiset.iter().len(), 11);
+        assert_eq!(
+            multiset.iter_counts().collect::<Vec<_>>(),
+            vec![(&'a', 5), (&'b', 2), (&'c', 1), (&'d', 1), (&'r', 2)]
+        );
+    }
+
+    #[test]
+    fn select_counts_repetitions() {
+        let multiset = sample();
+
+        assert_eq!(multiset.select(0), Some(&'a'));
+        assert_eq!(multiset.select(4), Some(&'a'));
+        assert_eq!(multiset.select(5), Some(&'b'));
+        assert_eq!(multiset.select(multiset.total_len() / 2), Some(&'b'));
+        assert_eq!(multiset.select(10), Some(&'r'));
+        assert_eq!(multiset.select(11), None);
+
+        // every select() agrees with the repeating iterator
+        for (index, value) in multiset.iter().enumerate() {
+            assert_eq!(multiset.select(index), Some(value));
+        }
+    }
+}
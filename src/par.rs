@@ -0,0 +1,401 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains parallel iteration and bulk operations using rayon (behind "rayon" feature)
+//
+// Parallel iterators split the work at subtree boundaries - a part of the tree is a list of pieces in key order,
+// each one being either a single entry or a whole subtree. Splitting expands a subtree into its left subtree,
+// its own entry and its right subtree, then cuts the list in half. Each part is then walked with the regular
+// in-order iterators, so the order of entries is kept for consumers which care about it (e.g. collect())
+//
+// Bulk insertion sorts and dedupes the new entries in parallel, merges them with the existing ones
+// and rebuilds the tree balanced, instead of inserting entries one by one
+
+use std::cmp::Ordering;
+
+use rayon::{
+    iter::{
+        FromParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator,
+        plumbing::{Folder, UnindexedConsumer, UnindexedProducer, bridge_unindexed},
+    },
+    slice::ParallelSliceMut,
+};
+
+use crate::{
+    BSTMap, Comparator, Node, NodeRef,
+    iter::{
+        BSTMapByrefInorderIter, BSTMapByrefInorderIterMut, BSTMapConsumingInorderIter, Visited,
+    },
+};
+
+// Iteration needs no comparator, the order is already in the shape of the tree
+impl<K: Sync, V: Sync, C> BSTMap<K, V, C> {
+    pub fn par_iter(&self) -> BSTMapParIter<'_, K, V> {
+        BSTMapParIter {
+            pieces: self
+                .head
+                .as_deref()
+                .map(Piece::Subtree)
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+impl<K: Send + Sync, V: Send, C> BSTMap<K, V, C> {
+    pub fn par_iter_mut(&mut self) -> BSTMapParIterMut<'_, K, V> {
+        BSTMapParIterMut {
+            pieces: self
+                .head
+                .as_deref_mut()
+                .map(PieceMut::Subtree)
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+// Part of the tree to be visited - a single entry or a whole subtree
+enum Piece<'a, K, V> {
+    Entry(&'a K, &'a V),
+    Subtree(&'a Node<K, V>),
+}
+
+// Parallel In-Order iterator over the BST
+pub struct BSTMapParIter<'a, K, V> {
+    pieces: Vec<Piece<'a, K, V>>,
+}
+
+impl<'a, K: Sync, V: Sync> ParallelIterator for BSTMapParIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge_unindexed(self, consumer)
+    }
+}
+
+impl<'a, K: Sync, V: Sync> UnindexedProducer for BSTMapParIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn split(mut self) -> (Self, Option<Self>) {
+        if let [Piece::Subtree(node)] = self.pieces[..] {
+            self.pieces = node
+                .left
+                .as_deref()
+                .map(Piece::Subtree)
+                .into_iter()
+                .chain([Piece::Entry(&node.key, &node.value)])
+                .chain(node.right.as_deref().map(Piece::Subtree))
+                .collect();
+        }
+
+        if self.pieces.len() < 2 {
+            return (self, None);
+        }
+
+        let right = self.pieces.split_off(self.pieces.len() / 2);
+        (self, Some(Self { pieces: right }))
+    }
+
+    fn fold_with<F: Folder<Self::Item>>(self, mut folder: F) -> F {
+        for piece in self.pieces {
+            folder = match piece {
+                Piece::Entry(key, value) => folder.consume((key, value)),
+                Piece::Subtree(node) => folder.consume_iter(BSTMapByrefInorderIter {
+                    stack: vec![(node, Visited::None)],
+                }),
+            };
+
+            if folder.full() {
+                break;
+            }
+        }
+
+        folder
+    }
+}
+
+// Same as Piece, but with mutable access to values
+enum PieceMut<'a, K, V> {
+    Entry(&'a K, &'a mut V),
+    Subtree(&'a mut Node<K, V>),
+}
+
+// Parallel In-Order iterator over the BST with mutable access to values
+pub struct BSTMapParIterMut<'a, K, V> {
+    pieces: Vec<PieceMut<'a, K, V>>,
+}
+
+impl<'a, K: Send + Sync, V: Send> ParallelIterator for BSTMapParIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge_unindexed(self, consumer)
+    }
+}
+
+impl<'a, K: Send + Sync, V: Send> UnindexedProducer for BSTMapParIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn split(mut self) -> (Self, Option<Self>) {
+        if matches!(self.pieces[..], [PieceMut::Subtree(_)]) {
+            // pieces is exactly one subtree, checked by matches! above
+            let Some(PieceMut::Subtree(node)) = self.pieces.pop() else {
+                unreachable!()
+            };
+
+            let Node {
+                left,
+                right,
+                key,
+                value,
+            } = node;
+
+            self.pieces = left
+                .as_deref_mut()
+                .map(PieceMut::Subtree)
+                .into_iter()
+                .chain([PieceMut::Entry(&*key, value)])
+                .chain(right.as_deref_mut().map(PieceMut::Subtree))
+                .collect();
+        }
+
+        if self.pieces.len() < 2 {
+            return (self, None);
+        }
+
+        let right = self.pieces.split_off(self.pieces.len() / 2);
+        (self, Some(Self { pieces: right }))
+    }
+
+    fn fold_with<F: Folder<Self::Item>>(self, mut folder: F) -> F {
+        for piece in self.pieces {
+            folder = match piece {
+                PieceMut::Entry(key, value) => folder.consume((key, value)),
+                PieceMut::Subtree(node) => folder.consume_iter(BSTMapByrefInorderIterMut {
+                    stack: vec![(Some(node), None, None)],
+                }),
+            };
+
+            if folder.full() {
+                break;
+            }
+        }
+
+        folder
+    }
+}
+
+impl<'a, K: Sync, V: Sync, C> IntoParallelIterator for &'a BSTMap<K, V, C> {
+    type Iter = BSTMapParIter<'a, K, V>;
+
+    type Item = (&'a K, &'a V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<'a, K: Send + Sync, V: Send, C> IntoParallelIterator for &'a mut BSTMap<K, V, C> {
+    type Iter = BSTMapParIterMut<'a, K, V>;
+
+    type Item = (&'a K, &'a mut V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter_mut()
+    }
+}
+
+impl<K: Send, V: Send, C: Comparator<K> + Sync> ParallelExtend<(K, V)> for BSTMap<K, V, C> {
+    fn par_extend<I: IntoParallelIterator<Item = (K, V)>>(&mut self, par_iter: I) {
+        let mut new_entries: Vec<(K, V)> = par_iter.into_par_iter().collect();
+        let comparator = &self.comparator;
+
+        // Sort is stable, so out of duplicate keys the one inserted last ends up last
+        new_entries.par_sort_by(|(a, _), (b, _)| comparator.compare(a, b));
+        let new_entries = dedup_keep_last(new_entries, comparator);
+
+        // Merge with existing entries (new ones win), both are already sorted
+        // Walking the old tree needs no comparator, so only its nodes are moved out
+        let old = BSTMap {
+            head: self.head.take(),
+            length: std::mem::take(&mut self.length),
+            comparator: (),
+        };
+        let mut merged = Vec::with_capacity(new_entries.len() + old.length);
+        let mut old_entries = BSTMapConsumingInorderIter::new(old).peekable();
+
+        for (key, value) in new_entries {
+            while let Some((old_key, _)) = old_entries.peek() {
+                match self.comparator.compare(old_key, &key) {
+                    Ordering::Less => merged.extend(old_entries.next()),
+                    Ordering::Equal => {
+                        old_entries.next();
+                        break;
+                    }
+                    Ordering::Greater => break,
+                }
+            }
+
+            merged.push((key, value));
+        }
+        merged.extend(old_entries);
+
+        self.length = merged.len();
+        self.head = build_balanced(merged.len(), &mut merged.into_iter());
+    }
+}
+
+impl<K: Send, V: Send, C: Comparator<K> + Default + Sync> FromParallelIterator<(K, V)>
+    for BSTMap<K, V, C>
+{
+    fn from_par_iter<I: IntoParallelIterator<Item = (K, V)>>(par_iter: I) -> Self {
+        let mut bst = BSTMap::default();
+        bst.par_extend(par_iter);
+        bst
+    }
+}
+
+// Keeps only the last entry out of every run of equal keys
+fn dedup_keep_last<K, V, C: Comparator<K>>(sorted: Vec<(K, V)>, comparator: &C) -> Vec<(K, V)> {
+    let mut deduped: Vec<(K, V)> = Vec::with_capacity(sorted.len());
+
+    for (key, value) in sorted {
+        match deduped.last_mut() {
+            Some(last) if comparator.compare(&last.0, &key) == Ordering::Equal => {
+                *last = (key, value)
+            }
+            _ => deduped.push((key, value)),
+        }
+    }
+
+    deduped
+}
+
+// Builds a balanced subtree out of next len entries of a sorted iterator
+// Recursion depth is logarithmic, since both halves have the same size (+/- 1)
+fn build_balanced<K, V>(len: usize, entries: &mut impl Iterator<Item = (K, V)>) -> NodeRef<K, V> {
+    if len == 0 {
+        return None;
+    }
+
+    let left = build_balanced(len / 2, entries);
+    // unwrap is safe, iterator has at least len entries
+    let (key, value) = entries.next().unwrap();
+    let right = build_balanced(len - len / 2 - 1, entries);
+
+    Some(Box::new(Node {
+        left,
+        right,
+        key,
+        value,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::iter::{IntoParallelIterator, ParallelExtend, ParallelIterator};
+
+    use crate::{BSTMap, ReverseComparator};
+
+    fn height<K: Ord, V>(node: &crate::NodeRef<K, V>) -> usize {
+        match node {
+            None => 0,
+            Some(inner) => 1 + height(&inner.left).max(height(&inner.right)),
+        }
+    }
+
+    #[test]
+    fn par_iter_matches_sequential_order() {
+        let mut bst = BSTMap::new();
+
+        // Pseudo-random insertion order, so that the tree has some shape
+        for i in 0..10_000u64 {
+            let key = i.wrapping_mul(7919) % 10_007;
+            bst.insert(key, key * 2);
+        }
+
+        let parallel: Vec<_> = bst.par_iter().collect();
+        let sequential: Vec<_> = bst.iter().collect();
+
+        assert_eq!(parallel, sequential);
+        assert_eq!(
+            bst.par_iter().map(|(_, v)| *v).sum::<u64>(),
+            bst.iter().map(|(_, v)| *v).sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn par_iter_mut_updates_every_value() {
+        let mut bst: BSTMap<u32, u32> = (0..5000).into_par_iter().map(|i| (i, i)).collect();
+
+        bst.par_iter_mut().for_each(|(k, v)| *v += k);
+
+        assert!(bst.iter().all(|(k, v)| *v == 2 * k));
+    }
+
+    #[test]
+    fn from_par_iter_dedupes_and_builds_balanced() {
+        let bst: BSTMap<u32, u32> = (0..4096u32)
+            .into_par_iter()
+            .map(|i| (i % 1024, i))
+            .collect();
+
+        assert_eq!(bst.len(), 1024);
+        assert_eq!(height(&bst.head), 11);
+
+        // Out of duplicates the last one in iteration order wins, like with sequential inserts
+        assert_eq!(bst.get(&5), Some(&(3 * 1024 + 5)));
+    }
+
+    #[test]
+    fn par_extend_merges_with_existing_entries() {
+        let mut bst = BSTMap::new();
+        bst.insert(1, "old");
+        bst.insert(3, "old");
+        bst.insert(5, "old");
+
+        bst.par_extend(vec![(2, "new"), (3, "new"), (6, "new")]);
+
+        assert_eq!(
+            bst.iter().collect::<Vec<_>>(),
+            vec![
+                (&1, &"old"),
+                (&2, &"new"),
+                (&3, &"new"),
+                (&5, &"old"),
+                (&6, &"new")
+            ]
+        );
+        assert_eq!(bst.len(), 5);
+    }
+
+    #[test]
+    fn par_extend_and_par_iter_use_comparator() {
+        let mut bst = BSTMap::with_comparator(ReverseComparator);
+        bst.insert(2, "old");
+        bst.insert(4, "old");
+
+        bst.par_extend(vec![(1, "new"), (4, "new"), (3, "new"), (1, "newer")]);
+
+        let entries: Vec<_> = bst.par_iter().collect();
+        assert_eq!(
+            entries,
+            vec![(&4, &"new"), (&3, &"new"), (&2, &"old"), (&1, &"newer")]
+        );
+        assert_eq!(bst.validate(), Ok(()));
+    }
+}
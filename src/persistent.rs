@@ -0,0 +1,624 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains persistent (structurally shared) variant of BSTMap
+//
+// Nodes are linked with Arc instead of Box, so clone() only bumps the reference count of the root.
+// Mutations copy the nodes on the path from the root to the modified node (Arc::make_mut copies a node
+// only if someone else holds it too), all the other subtrees are shared with older versions
+
+use std::{
+    cmp::Ordering,
+    mem,
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+};
+
+// Shorthand for a reference to an Arc'ed node that may or may not be there
+type PersistentNodeRef<K, V> = Option<Arc<PersistentNode<K, V>>>;
+
+#[derive(Clone)]
+struct PersistentNode<K, V> {
+    left: PersistentNodeRef<K, V>,
+    right: PersistentNodeRef<K, V>,
+    key: K,
+    value: V,
+}
+
+impl<K: Clone, V: Clone> PersistentNode<K, V> {
+    // Takes the node out of the Arc, copying it if it is shared
+    fn into_owned(node: Arc<Self>) -> Self {
+        Arc::try_unwrap(node).unwrap_or_else(|shared| (*shared).clone())
+    }
+}
+
+pub struct PersistentBSTMap<K: Ord, V> {
+    head: PersistentNodeRef<K, V>,
+    length: usize,
+}
+
+impl<K: Ord, V> PersistentBSTMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            head: None,
+            length: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.get(&key).is_some()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let mut current_node = &self.head;
+
+        while let Some(inner) = current_node.as_ref() {
+            current_node = match inner.key.cmp(key) {
+                Ordering::Less => &inner.right,
+                Ordering::Greater => &inner.left,
+                Ordering::Equal => return Some(&inner.value),
+            }
+        }
+
+        None
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let mut current_node = self.head.as_ref()?;
+
+        while let Some(left_node) = current_node.left.as_ref() {
+            current_node = left_node;
+        }
+
+        Some((&current_node.key, &current_node.value))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut current_node = self.head.as_ref()?;
+
+        while let Some(right_node) = current_node.right.as_ref() {
+            current_node = right_node;
+        }
+
+        Some((&current_node.key, &current_node.value))
+    }
+
+    // Whether both maps are the very same version (share the root), without comparing contents
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.head, &other.head) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    pub fn iter_inorder(&self) -> PersistentBSTMapIter<'_, K, V> {
+        self.range(..)
+    }
+
+    pub fn iter(&self) -> PersistentBSTMapIter<'_, K, V> {
+        self.iter_inorder()
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> PersistentBSTMapIter<'_, K, V> {
+        PersistentBSTMapIter::new(self, range)
+    }
+}
+
+// Mutations need to copy nodes, so they require the keys and values to be Clone
+impl<K: Ord + Clone, V: Clone> PersistentBSTMap<K, V> {
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut current_node = &mut self.head;
+
+        while current_node.is_some() {
+            // unwrap is safe inside the loop, since current_node is Some
+            let inner = Arc::make_mut(current_node.as_mut().unwrap());
+
+            current_node = match inner.key.cmp(&key) {
+                Ordering::Less => &mut inner.right,
+                Ordering::Greater => &mut inner.left,
+                Ordering::Equal => return Some(mem::replace(&mut inner.value, value)),
+            }
+        }
+
+        *current_node = Some(Arc::new(PersistentNode {
+            left: None,
+            right: None,
+            key,
+            value,
+        }));
+        self.length += 1;
+        None
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        // Check first, so that looking for a missing key does not copy anything
+        self.get(key)?;
+
+        let mut current_node = &mut self.head;
+
+        while let Some(inner) = current_node.as_mut() {
+            let inner = Arc::make_mut(inner);
+
+            current_node = match inner.key.cmp(key) {
+                Ordering::Less => &mut inner.right,
+                Ordering::Greater => &mut inner.left,
+                Ordering::Equal => return Some(&mut inner.value),
+            }
+        }
+
+        None
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        // Check first, so that removing a missing key does not copy anything
+        self.get(&key)?;
+
+        let mut current_node = &mut self.head;
+
+        // unwrap is safe, key is in the map so the path does not end before reaching it
+        loop {
+            let ordering = current_node.as_ref().unwrap().key.cmp(&key);
+
+            if ordering == Ordering::Equal {
+                break;
+            }
+
+            let inner = Arc::make_mut(current_node.as_mut().unwrap());
+            current_node = match ordering {
+                Ordering::Less => &mut inner.right,
+                _ => &mut inner.left,
+            };
+        }
+
+        self.length -= 1;
+
+        // unwrap is safe, current_node is the node being removed
+        let inner = Arc::make_mut(current_node.as_mut().unwrap());
+
+        // With at most one child, replace the node with that child
+        if inner.left.is_none() || inner.right.is_none() {
+            // unwrap is safe, current_node is Some
+            let PersistentNode {
+                left, right, value, ..
+            } = PersistentNode::into_owned(current_node.take().unwrap());
+
+            *current_node = left.or(right);
+            return Some(value);
+        }
+
+        // With two children, move the in-order successor into this node
+        // Node is already a private copy, so its key and value can simply be swapped out
+        // unwrap is safe, right subtree is not empty
+        let (successor_key, successor_value) = Self::pop_min(&mut inner.right).unwrap();
+        inner.key = successor_key;
+
+        Some(mem::replace(&mut inner.value, successor_value))
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let popped = Self::pop_min(&mut self.head)?;
+        self.length -= 1;
+        Some(popped)
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let mut current_node = &mut self.head;
+
+        while current_node.as_ref()?.right.is_some() {
+            // unwrap is safe, checked in the loop condition
+            current_node = &mut Arc::make_mut(current_node.as_mut().unwrap()).right;
+        }
+
+        let PersistentNode {
+            left, key, value, ..
+        } = PersistentNode::into_owned(current_node.take()?);
+        *current_node = left;
+        self.length -= 1;
+
+        Some((key, value))
+    }
+
+    // Returns a new version with the entry inserted, leaving this one untouched
+    pub fn inserted(&self, key: K, value: V) -> Self {
+        let mut new_version = self.clone();
+        new_version.insert(key, value);
+        new_version
+    }
+
+    // Returns a new version with the entry removed, leaving this one untouched
+    pub fn removed(&self, key: K) -> Self {
+        let mut new_version = self.clone();
+        new_version.remove(key);
+        new_version
+    }
+
+    // Detaches the leftmost node of the subtree, does not update length
+    fn pop_min(subtree: &mut PersistentNodeRef<K, V>) -> Option<(K, V)> {
+        let mut current_node = subtree;
+
+        while current_node.as_ref()?.left.is_some() {
+            // unwrap is safe, checked in the loop condition
+            current_node = &mut Arc::make_mut(current_node.as_mut().unwrap()).left;
+        }
+
+        let PersistentNode {
+            right, key, value, ..
+        } = PersistentNode::into_owned(current_node.take()?);
+        *current_node = right;
+
+        Some((key, value))
+    }
+}
+
+impl<K: Ord, V> Default for PersistentBSTMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// O(1) - new version shares the whole tree with this one
+impl<K: Ord, V> Clone for PersistentBSTMap<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            head: self.head.clone(),
+            length: self.length,
+        }
+    }
+}
+
+// Same as BSTMap, avoid recursive drop - nodes still used by other versions are not descended into
+impl<K: Ord, V> Drop for PersistentBSTMap<K, V> {
+    fn drop(&mut self) {
+        let mut stack: Vec<Arc<PersistentNode<K, V>>> = self.head.take().into_iter().collect();
+
+        while let Some(node) = stack.pop() {
+            if let Some(mut node) = Arc::into_inner(node) {
+                stack.extend(node.left.take());
+                stack.extend(node.right.take());
+            }
+        }
+    }
+}
+
+// Implements In-Order iteration over entries with keys within a range
+pub struct PersistentBSTMapIter<'a, K: Ord, V> {
+    // nodes whose key is yet to be returned, each one with its left subtree already explored
+    stack: Vec<&'a PersistentNode<K, V>>,
+    // last node within the range, iteration stops after it is returned
+    last: Option<&'a PersistentNode<K, V>>,
+}
+
+impl<'a, K: Ord, V> PersistentBSTMapIter<'a, K, V> {
+    fn new<R: RangeBounds<K>>(bst: &'a PersistentBSTMap<K, V>, range: R) -> Self {
+        let mut stack = Vec::new();
+        let mut current_node = &bst.head;
+
+        while let Some(inner) = current_node.as_ref() {
+            let is_after_start = match range.start_bound() {
+                Bound::Included(start) => inner.key >= *start,
+                Bound::Excluded(start) => inner.key > *start,
+                Bound::Unbounded => true,
+            };
+
+            current_node = if is_after_start {
+                stack.push(inner.as_ref());
+                &inner.left
+            } else {
+                &inner.right
+            };
+        }
+
+        let mut last = None;
+        let mut current_node = &bst.head;
+
+        while let Some(inner) = current_node.as_ref() {
+            let is_before_end = match range.end_bound() {
+                Bound::Included(end) => inner.key <= *end,
+                Bound::Excluded(end) => inner.key < *end,
+                Bound::Unbounded => true,
+            };
+
+            current_node = if is_before_end {
+                last = Some(inner.as_ref());
+                &inner.right
+            } else {
+                &inner.left
+            };
+        }
+
+        let is_empty = match (stack.last(), last) {
+            (Some(first), Some(last)) => first.key > last.key,
+            _ => true,
+        };
+
+        if is_empty {
+            stack.clear();
+        }
+
+        Self { stack, last }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for PersistentBSTMapIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current_node = self.stack.pop()?;
+
+        if self
+            .last
+            .is_some_and(|last| std::ptr::eq(last, current_node))
+        {
+            self.stack.clear();
+        } else {
+            let mut next_node = &current_node.right;
+            while let Some(inner) = next_node.as_ref() {
+                self.stack.push(inner.as_ref());
+                next_node = &inner.left;
+            }
+        }
+
+        Some((&current_node.key, &current_node.value))
+    }
+}
+
+// Consuming In-Order iterator, entries still shared with other versions are cloned
+pub struct PersistentBSTMapIntoIter<K, V> {
+    stack: Vec<Arc<PersistentNode<K, V>>>,
+}
+
+impl<K: Clone, V: Clone> PersistentBSTMapIntoIter<K, V> {
+    fn push_left_spine(&mut self, mut subtree: PersistentNodeRef<K, V>) {
+        while let Some(mut node) = subtree {
+            // Detach the left child from nodes we own, so that they can be unwrapped without copying
+            subtree = match Arc::get_mut(&mut node) {
+                Some(inner) => inner.left.take(),
+                None => node.left.clone(),
+            };
+
+            self.stack.push(node);
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Iterator for PersistentBSTMapIntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+
+        let PersistentNode {
+            right, key, value, ..
+        } = PersistentNode::into_owned(node);
+        self.push_left_spine(right);
+
+        Some((key, value))
+    }
+}
+
+// Same as PersistentBSTMap, nodes left on the stack still own their right subtrees,
+// so dropping the iterator early must not descend into them recursively
+impl<K, V> Drop for PersistentBSTMapIntoIter<K, V> {
+    fn drop(&mut self) {
+        while let Some(node) = self.stack.pop() {
+            if let Some(mut node) = Arc::into_inner(node) {
+                self.stack.extend(node.left.take());
+                self.stack.extend(node.right.take());
+            }
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> IntoIterator for PersistentBSTMap<K, V> {
+    type Item = (K, V);
+
+    type IntoIter = PersistentBSTMapIntoIter<K, V>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let mut iter = PersistentBSTMapIntoIter { stack: Vec::new() };
+        iter.push_left_spine(self.head.take());
+        iter
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a PersistentBSTMap<K, V> {
+    type Item = (&'a K, &'a V);
+
+    type IntoIter = PersistentBSTMapIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_inorder()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> FromIterator<(K, V)> for PersistentBSTMap<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut bst = Self::new();
+
+        for (k, v) in iter {
+            bst.insert(k, v);
+        }
+
+        bst
+    }
+}
+
+impl<K: Ord + Clone, V: Clone, const N: usize> From<[(K, V); N]> for PersistentBSTMap<K, V> {
+    fn from(array: [(K, V); N]) -> Self {
+        array.into_iter().collect()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Extend<(K, V)> for PersistentBSTMap<K, V> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{PersistentBSTMap, PersistentNode};
+
+    fn sample() -> PersistentBSTMap<u32, String> {
+        [
+            (10, "hello"),
+            (5, "hi"),
+            (15, "bye"),
+            (2, "leaf_node_child"),
+            (13, "left_child"),
+            (20, "right_child"),
+            (12, "left_child_subtree_l"),
+            (14, "left_child_subtree_r"),
+        ]
+        .map(|(k, v)| (k, v.to_string()))
+        .into()
+    }
+
+    fn keys(bst: &PersistentBSTMap<u32, String>) -> Vec<u32> {
+        bst.iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn clone_is_shared_until_modified() {
+        let version_1 = sample();
+        let mut version_2 = version_1.clone();
+
+        assert!(version_1.ptr_eq(&version_2));
+
+        version_2.insert(3, "three".to_string());
+        version_2.remove(15);
+        *version_2.get_mut(&10).unwrap() = "modified".to_string();
+
+        assert!(!version_1.ptr_eq(&version_2));
+        assert_eq!(keys(&version_1), vec![2, 5, 10, 12, 13, 14, 15, 20]);
+        assert_eq!(keys(&version_2), vec![2, 3, 5, 10, 12, 13, 14, 20]);
+        assert_eq!(version_1.get(&10).unwrap(), "hello");
+        assert_eq!(version_2.get(&10).unwrap(), "modified");
+        assert_eq!(version_1.len(), 8);
+        assert_eq!(version_2.len(), 8);
+    }
+
+    #[test]
+    fn untouched_subtrees_are_shared() {
+        let version_1 = sample();
+        let version_2 = version_1.inserted(1, "one".to_string());
+
+        // Insertion went to the left of the root, so the right subtree is the same allocation
+        let right_1 = version_1.head.as_ref().unwrap().right.as_ref().unwrap();
+        let right_2 = version_2.head.as_ref().unwrap().right.as_ref().unwrap();
+
+        assert!(Arc::ptr_eq(right_1, right_2));
+        assert!(version_1.get(&1).is_none());
+        assert!(version_2.get(&1).is_some());
+    }
+
+    #[test]
+    fn removal_covers_all_cases() {
+        let mut bst = sample();
+
+        // leaf, node with one child, node with two children, root
+        assert_eq!(bst.remove(12).unwrap(), "left_child_subtree_l");
+        assert_eq!(bst.remove(5).unwrap(), "hi");
+        assert_eq!(bst.remove(15).unwrap(), "bye");
+        assert_eq!(bst.remove(10).unwrap(), "hello");
+        assert!(bst.remove(10).is_none());
+
+        assert_eq!(keys(&bst), vec![2, 13, 14, 20]);
+        assert_eq!(bst.len(), 4);
+    }
+
+    #[test]
+    fn removed_leaves_original_untouched() {
+        let version_1 = sample();
+        let version_2 = version_1.removed(10).removed(2);
+
+        assert_eq!(keys(&version_1), vec![2, 5, 10, 12, 13, 14, 15, 20]);
+        assert_eq!(keys(&version_2), vec![5, 12, 13, 14, 15, 20]);
+    }
+
+    #[test]
+    fn pop_and_range_work_like_in_bstmap() {
+        let mut bst = sample();
+        let snapshot = bst.clone();
+
+        assert_eq!(bst.pop_first().unwrap().0, 2);
+        assert_eq!(bst.pop_last().unwrap().0, 20);
+        assert_eq!(bst.first_key_value().unwrap().0, &5);
+        assert_eq!(bst.last_key_value().unwrap().0, &15);
+        assert_eq!(
+            bst.range(11..=14).map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![12, 13, 14]
+        );
+        assert_eq!(snapshot.len(), 8);
+    }
+
+    #[test]
+    fn into_iter_yields_entries_in_order_even_if_shared() {
+        let bst = sample();
+        let snapshot = bst.clone();
+
+        let owned: Vec<_> = bst.into_iter().map(|(k, _)| k).collect();
+
+        assert_eq!(owned, vec![2, 5, 10, 12, 13, 14, 15, 20]);
+        assert_eq!(keys(&snapshot), owned);
+    }
+
+    #[test]
+    fn dropping_degenerate_tree_does_not_overflow_the_stack() {
+        let bst: PersistentBSTMap<u32, u32> = (0..10_000).map(|i| (i, i)).collect();
+        let snapshot = bst.inserted(10_000, 10_000);
+
+        drop(bst);
+        assert_eq!(snapshot.len(), 10_001);
+    }
+
+    #[test]
+    fn dropping_into_iter_early_does_not_overflow_the_stack() {
+        // Built by hand, inserting sorted keys one by one would take quadratic time
+        let len = 200_000;
+        let mut head = None;
+        for key in (0..len).rev() {
+            head = Some(Arc::new(PersistentNode {
+                left: None,
+                right: head,
+                key,
+                value: key,
+            }));
+        }
+        let bst = PersistentBSTMap { head, length: len };
+
+        let mut iter = bst.into_iter();
+        assert_eq!(iter.next(), Some((0, 0)));
+        drop(iter);
+    }
+}
@@ -0,0 +1,262 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains thread-safe ordered map split into range shards
+//
+// Key space is divided by sorted boundary keys - shard i holds keys in [boundaries[i - 1], boundaries[i]),
+// first and last shards are open ended. Each shard is a BSTMap behind its own RwLock, so threads working
+// on different parts of the key space do not contend. Since shards are ordered, iteration simply visits
+// them one after another, and range scans only lock shards which overlap the range
+
+use std::{
+    ops::{Bound, RangeBounds},
+    sync::RwLock,
+    vec,
+};
+
+use crate::BSTMap;
+
+pub struct ShardedBSTMap<K: Ord, V> {
+    boundaries: Vec<K>,
+    shards: Vec<RwLock<BSTMap<K, V>>>,
+}
+
+impl<K: Ord, V> ShardedBSTMap<K, V> {
+    // Map with a single shard, which behaves like BSTMap behind a RwLock
+    pub fn new() -> Self {
+        Self::with_boundaries(Vec::new())
+    }
+
+    // Map with boundaries.len() + 1 shards, duplicate boundaries are ignored
+    pub fn with_boundaries(mut boundaries: Vec<K>) -> Self {
+        boundaries.sort();
+        boundaries.dedup();
+
+        let shards = (0..=boundaries.len())
+            .map(|_| RwLock::new(BSTMap::new()))
+            .collect();
+
+        Self { boundaries, shards }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    // Sum of lengths of all shards, not atomic with respect to concurrent writers
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            shard.write().unwrap().clear();
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shard_for(&key).write().unwrap().insert(key, value)
+    }
+
+    pub fn remove(&self, key: K) -> Option<V> {
+        self.shard_for(&key).write().unwrap().remove(key)
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.shard_for(key).read().unwrap().get(key).is_some()
+    }
+
+    // Calls f with the value while the shard is read-locked, avoiding a clone
+    pub fn get_with<R>(&self, key: &K, f: impl FnOnce(Option<&V>) -> R) -> R {
+        f(self.shard_for(key).read().unwrap().get(key))
+    }
+
+    // Calls f with the value while the shard is write-locked
+    pub fn update<R>(&self, key: &K, f: impl FnOnce(Option<&mut V>) -> R) -> R {
+        f(self.shard_for(key).write().unwrap().get_mut(key))
+    }
+
+    fn shard_index(&self, key: &K) -> usize {
+        self.boundaries.partition_point(|boundary| boundary <= key)
+    }
+
+    fn shard_for(&self, key: &K) -> &RwLock<BSTMap<K, V>> {
+        &self.shards[self.shard_index(key)]
+    }
+}
+
+// References cannot outlive the lock guard, so reads and iteration hand out clones
+impl<K: Ord + Clone, V: Clone> ShardedBSTMap<K, V> {
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.get_with(key, |value| value.cloned())
+    }
+
+    // In-order iteration over all entries
+    pub fn iter(&self) -> ShardedBSTMapIter<'_, K, V> {
+        self.range(..)
+    }
+
+    // In-order iteration over entries within the range, only shards overlapping it are locked
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> ShardedBSTMapIter<'_, K, V> {
+        let first_shard = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) => self.shard_index(start),
+            Bound::Unbounded => 0,
+        };
+
+        let last_shard = match range.end_bound() {
+            Bound::Included(end) | Bound::Excluded(end) => self.shard_index(end),
+            Bound::Unbounded => self.shards.len() - 1,
+        };
+
+        ShardedBSTMapIter {
+            map: self,
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            next_shard: first_shard,
+            last_shard,
+            buffer: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<K: Ord, V> Default for ShardedBSTMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Iterates over shards one by one
+// Entries of a shard are copied out under its read lock, which is released before they are returned,
+// so the iterator never holds a lock between calls to next(). As a consequence each shard is seen
+// consistently, but writes to shards not yet visited may or may not be observed
+pub struct ShardedBSTMapIter<'a, K: Ord, V> {
+    map: &'a ShardedBSTMap<K, V>,
+    start: Bound<K>,
+    end: Bound<K>,
+    next_shard: usize,
+    last_shard: usize,
+    buffer: vec::IntoIter<(K, V)>,
+}
+
+impl<'a, K: Ord + Clone, V: Clone> Iterator for ShardedBSTMapIter<'a, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.buffer.next() {
+                return Some(entry);
+            }
+
+            if self.next_shard > self.last_shard {
+                return None;
+            }
+
+            let shard = self.map.shards[self.next_shard].read().unwrap();
+            self.buffer = shard
+                .range((self.start.as_ref(), self.end.as_ref()))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect::<Vec<_>>()
+                .into_iter();
+            self.next_shard += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::ShardedBSTMap;
+
+    #[test]
+    fn keys_land_in_their_shards() {
+        let bst = ShardedBSTMap::with_boundaries(vec![20, 10, 10]);
+
+        assert_eq!(bst.shard_count(), 3);
+
+        for key in [5, 10, 15, 20, 25] {
+            bst.insert(key, key * 2);
+        }
+
+        assert_eq!(bst.shards[0].read().unwrap().len(), 1);
+        assert_eq!(bst.shards[1].read().unwrap().len(), 2);
+        assert_eq!(bst.shards[2].read().unwrap().len(), 2);
+        assert_eq!(bst.get(&15), Some(30));
+        assert!(bst.contains(&20));
+        assert_eq!(bst.remove(20), Some(40));
+        assert!(!bst.contains(&20));
+        assert_eq!(bst.len(), 4);
+    }
+
+    #[test]
+    fn iteration_is_ordered_across_shards() {
+        let bst = ShardedBSTMap::with_boundaries(vec![25, 50, 75]);
+
+        for key in (0..100).rev() {
+            bst.insert(key, ());
+        }
+
+        let keys: Vec<_> = bst.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, (0..100).collect::<Vec<_>>());
+
+        let keys: Vec<_> = bst.range(20..=55).map(|(k, _)| k).collect();
+        assert_eq!(keys, (20..=55).collect::<Vec<_>>());
+
+        let keys: Vec<_> = bst.range(..25).map(|(k, _)| k).collect();
+        assert_eq!(keys, (0..25).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn range_does_not_lock_other_shards() {
+        let bst = ShardedBSTMap::with_boundaries(vec![10, 20]);
+
+        for key in 0..30 {
+            bst.insert(key, key);
+        }
+
+        // Writer holds the last shard, scan of the first two must still complete
+        let _guard = bst.shards[2].write().unwrap();
+        assert_eq!(bst.range(5..15).count(), 10);
+    }
+
+    #[test]
+    fn concurrent_writers_do_not_lose_entries() {
+        let bst = ShardedBSTMap::with_boundaries(vec![1000, 2000, 3000]);
+
+        thread::scope(|scope| {
+            for thread_id in 0..4 {
+                let bst = &bst;
+                scope.spawn(move || {
+                    for i in 0..500 {
+                        let key = i * 8 + thread_id;
+                        bst.insert(key, thread_id);
+                        bst.update(&key, |value| *value.unwrap() += 1);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(bst.len(), 2000);
+        assert!(bst.iter().all(|(k, v)| v == k % 8 + 1));
+    }
+}
@@ -0,0 +1,248 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains snapshots - full dumps of BSTMap contents used to compact the write-ahead log
+//
+// Layout of the snapshot file:
+// - 8 bytes of magic (SNAPSHOT_MAGIC)
+// - u64 number of entries
+// - entries, each being u8 child flags (CHILD_LEFT, CHILD_RIGHT), length-prefixed key
+//   and length-prefixed value
+// - u32 CRC-32 of everything above
+//
+// Entries are stored in breadth-first order along with which children they have,
+// so the exact same tree is linked back together in O(n) - without comparing any keys,
+// which matters for degenerate trees where inserting entries one by one would take O(n^2)
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    BSTMap, Comparator, Node,
+    codec::{ByteReader, Decode, Encode, crc32, invalid_data, put_framed, put_u32, put_u64},
+    iter::BSTMapByrefBreadthfirstIter,
+};
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"RBSTSNP2";
+
+const CHILD_LEFT: u8 = 1;
+const CHILD_RIGHT: u8 = 2;
+
+// Writes the snapshot under a temporary name first and renames it once it is synced,
+// so a file at path is either complete or not there at all
+pub(crate) fn write_snapshot<K: Encode, V: Encode, C: Comparator<K>>(
+    path: &Path,
+    bst: &BSTMap<K, V, C>,
+) -> io::Result<()> {
+    let mut contents = Vec::new();
+    contents.extend_from_slice(SNAPSHOT_MAGIC);
+    put_u64(&mut contents, bst.len() as u64);
+
+    let mut nodes = BSTMapByrefBreadthfirstIter::new(bst);
+    while let Some(node) = nodes.next_node() {
+        let flags =
+            node.left.is_some() as u8 * CHILD_LEFT + node.right.is_some() as u8 * CHILD_RIGHT;
+        contents.push(flags);
+        put_framed(&mut contents, &node.key);
+        put_framed(&mut contents, &node.value);
+    }
+
+    let checksum = crc32(&contents);
+    put_u32(&mut contents, checksum);
+
+    // Left behind if we crash before the rename, DurableBSTMap removes such files on open
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)
+}
+
+pub(crate) fn read_snapshot<K: Decode, V: Decode, C: Comparator<K>>(
+    path: &Path,
+    comparator: C,
+) -> io::Result<BSTMap<K, V, C>> {
+    decode_snapshot(&fs::read(path)?, comparator)
+}
+
+// Rebuilds the map out of the contents of a snapshot file
+pub(crate) fn decode_snapshot<K: Decode, V: Decode, C: Comparator<K>>(
+    contents: &[u8],
+    comparator: C,
+) -> io::Result<BSTMap<K, V, C>> {
+    if contents.len() < SNAPSHOT_MAGIC.len() + 8 + 4 || !contents.starts_with(SNAPSHOT_MAGIC) {
+        return Err(invalid_data("file is not a snapshot"));
+    }
+
+    let (body, checksum) = contents.split_at(contents.len() - 4);
+    // unwrap is safe, checksum is exactly 4 bytes
+    if crc32(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(invalid_data("snapshot checksum mismatch"));
+    }
+
+    let mut reader = ByteReader::new(&body[SNAPSHOT_MAGIC.len()..]);
+    let count = reader.read_u64()?;
+
+    // Children of a node come later in breadth-first order, at the next free positions
+    // (counting from 1, 0 being the root) - so the positions are known before the children are read
+    let mut nodes = Vec::new();
+    let mut children = Vec::new();
+    let mut next_position = 1u64;
+
+    for position in 0..count {
+        // Every node but the root has to be claimed as a child by one of the nodes before it
+        if position >= next_position {
+            return Err(invalid_data("snapshot node has no parent"));
+        }
+
+        let flags = reader.read_u8()?;
+        if flags & !(CHILD_LEFT | CHILD_RIGHT) != 0 {
+            return Err(invalid_data("invalid child flags in snapshot"));
+        }
+
+        let mut child_position = |flag| {
+            (flags & flag != 0).then(|| {
+                next_position += 1;
+                next_position - 1
+            })
+        };
+        children.push((child_position(CHILD_LEFT), child_position(CHILD_RIGHT)));
+
+        let key = K::decode(reader.read_framed()?)?;
+        let value = V::decode(reader.read_framed()?)?;
+        nodes.push(Some(Box::new(Node::new(key, value))));
+    }
+
+    if !reader.is_empty() || next_position != (nodes.len() as u64).max(1) {
+        return Err(invalid_data("snapshot entry count mismatch"));
+    }
+
+    // Children have greater positions than their parents, so going backwards
+    // every subtree is complete by the time it is attached
+    for position in (0..nodes.len()).rev() {
+        let (left, right) = children[position];
+        let left = left.and_then(|left| nodes[left as usize].take());
+        let right = right.and_then(|right| nodes[right as usize].take());
+
+        // unwrap is safe, a node is only taken by its parent, which comes earlier
+        let node = nodes[position].as_mut().unwrap();
+        node.left = left;
+        node.right = right;
+    }
+
+    let mut bst = BSTMap::with_comparator(comparator);
+    bst.head = nodes.into_iter().next().flatten();
+    bst.length = count as usize;
+
+    // Keys were not compared while linking, a snapshot with valid checksum but keys out of order
+    // (e.g. written with a different comparator) would otherwise give a broken tree
+    if bst.validate().is_err() {
+        return Err(invalid_data("snapshot keys are out of order"));
+    }
+
+    Ok(bst)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{decode_snapshot, read_snapshot, write_snapshot};
+    use crate::{BSTMap, Node, OrdComparator, ReverseComparator, durable::tests::temp_path};
+
+    #[test]
+    fn snapshot_roundtrip_preserves_contents_and_shape() {
+        let path = temp_path("snapshot_roundtrip");
+
+        let bst: BSTMap<u32, String> = [
+            (13, "hello"),
+            (15, "bye"),
+            (7, "test"),
+            (2, "test2"),
+            (8, "high number"),
+        ]
+        .map(|(k, v)| (k, v.to_string()))
+        .into();
+
+        write_snapshot(&path, &bst).unwrap();
+        let loaded = read_snapshot::<u32, String, _>(&path, OrdComparator).unwrap();
+
+        assert_eq!(
+            loaded.iter_breadthfirst().collect::<Vec<_>>(),
+            bst.iter_breadthfirst().collect::<Vec<_>>()
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn corrupted_snapshot_is_rejected() {
+        let path = temp_path("snapshot_corrupted");
+
+        let bst: BSTMap<u32, u32> = [(1, 10), (2, 20)].into();
+        write_snapshot(&path, &bst).unwrap();
+
+        let mut contents = fs::read(&path).unwrap();
+        contents[10] ^= 0x01;
+        fs::write(&path, &contents).unwrap();
+
+        assert!(read_snapshot::<u32, u32, _>(&path, OrdComparator).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_of_degenerate_tree_is_read_in_linear_time() {
+        let path = temp_path("snapshot_degenerate");
+
+        // Built by hand, inserting sorted keys one by one would take quadratic time
+        let len = 200_000;
+        let mut bst = BSTMap::<u32, bool>::new();
+        let mut slot = &mut bst.head;
+        for key in 0..len {
+            slot = &mut slot.insert(Box::new(Node::new(key, true))).right;
+        }
+        bst.length = len as usize;
+
+        write_snapshot(&path, &bst).unwrap();
+        let loaded = read_snapshot::<u32, bool, _>(&path, OrdComparator).unwrap();
+
+        assert_eq!(loaded.len(), len as usize);
+        assert!(loaded.keys().copied().eq(0..len));
+        assert_eq!(loaded.stats().height, len as usize);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_with_keys_out_of_order_is_rejected() {
+        let bst: BSTMap<u32, u32> = [(2, 20), (1, 10), (3, 30)].into();
+
+        let path = temp_path("snapshot_out_of_order");
+        write_snapshot(&path, &bst).unwrap();
+        let contents = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // Written in ascending order, read back in descending order
+        assert!(decode_snapshot::<u32, u32, _>(&contents, ReverseComparator).is_err());
+        assert!(decode_snapshot::<u32, u32, _>(&contents, OrdComparator).is_ok());
+    }
+}
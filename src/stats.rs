@@ -0,0 +1,163 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains statistics describing the shape of the tree, e.g. to compare it before and after rebalancing
+
+use crate::{BSTMap, iter::BSTMapByrefBreadthfirstIter};
+
+// Depths start at 0 for the root, height is the number of levels (0 for an empty tree)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeStats {
+    pub len: usize,
+    pub height: usize,
+    // None for an empty tree
+    pub min_leaf_depth: Option<usize>,
+    pub max_leaf_depth: Option<usize>,
+    // 0.0 for an empty tree
+    pub average_depth: f64,
+    // Number of nodes at each depth
+    pub levels: Vec<usize>,
+    // Nodes with no children
+    pub leaves: usize,
+    pub one_child: usize,
+    pub two_children: usize,
+    // Height divided by the lowest height possible for len nodes (ceil(log2(len + 1))),
+    // 1.0 for a perfectly balanced (or empty) tree, up to len / log2(len + 1) for a degenerate one
+    pub balance_factor: f64,
+}
+
+impl<K, V, C> BSTMap<K, V, C> {
+    // Walks the tree once, level by level
+    pub fn stats(&self) -> TreeStats {
+        let mut iter = BSTMapByrefBreadthfirstIter::new(self);
+
+        let mut levels = vec![];
+        let mut min_leaf_depth = None;
+        let mut max_leaf_depth = None;
+        let mut depth_sum = 0;
+        let mut children_counts = [0; 3];
+
+        while !iter.queue.is_empty() {
+            let depth = levels.len();
+            let level_len = iter.queue.len();
+
+            for _ in 0..level_len {
+                // unwrap is safe, the queue holds level_len nodes of this level
+                let node = iter.next_node().unwrap();
+                let children = node.left.is_some() as usize + node.right.is_some() as usize;
+
+                children_counts[children] += 1;
+                if children == 0 {
+                    min_leaf_depth.get_or_insert(depth);
+                    max_leaf_depth = Some(depth);
+                }
+            }
+
+            depth_sum += depth * level_len;
+            levels.push(level_len);
+        }
+
+        let height = levels.len();
+        let optimal_height = (usize::BITS - self.length.leading_zeros()) as usize;
+
+        TreeStats {
+            len: self.length,
+            height,
+            min_leaf_depth,
+            max_leaf_depth,
+            average_depth: if self.length == 0 {
+                0.0
+            } else {
+                depth_sum as f64 / self.length as f64
+            },
+            levels,
+            leaves: children_counts[0],
+            one_child: children_counts[1],
+            two_children: children_counts[2],
+            balance_factor: if self.length == 0 {
+                1.0
+            } else {
+                height as f64 / optimal_height as f64
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BSTMap;
+
+    use super::TreeStats;
+
+    #[test]
+    fn stats_of_empty_tree() {
+        assert_eq!(
+            BSTMap::<u32, ()>::new().stats(),
+            TreeStats {
+                len: 0,
+                height: 0,
+                min_leaf_depth: None,
+                max_leaf_depth: None,
+                average_depth: 0.0,
+                levels: vec![],
+                leaves: 0,
+                one_child: 0,
+                two_children: 0,
+                balance_factor: 1.0,
+            }
+        );
+    }
+
+    #[test]
+    fn stats_of_unbalanced_tree() {
+        //       8
+        //     /   \
+        //    4     9
+        //   / \
+        //  2   6
+        //       \
+        //        7
+        let bst: BSTMap<u32, ()> = [8, 4, 9, 2, 6, 7].into_iter().map(|k| (k, ())).collect();
+
+        assert_eq!(
+            bst.stats(),
+            TreeStats {
+                len: 6,
+                height: 4,
+                min_leaf_depth: Some(1),
+                max_leaf_depth: Some(3),
+                // depths: 0, 1, 1, 2, 2, 3
+                average_depth: 9.0 / 6.0,
+                levels: vec![1, 2, 2, 1],
+                leaves: 3,
+                one_child: 1,
+                two_children: 2,
+                balance_factor: 4.0 / 3.0,
+            }
+        );
+    }
+
+    #[test]
+    fn balance_factor_of_perfect_and_degenerate_trees() {
+        let perfect: BSTMap<u32, ()> = [4, 2, 6, 1, 3, 5, 7].into_iter().map(|k| (k, ())).collect();
+        let degenerate: BSTMap<u32, ()> = (0..7).map(|k| (k, ())).collect();
+
+        assert_eq!(perfect.stats().balance_factor, 1.0);
+        assert_eq!(perfect.stats().levels, vec![1, 2, 4]);
+        assert_eq!(degenerate.stats().balance_factor, 7.0 / 3.0);
+        assert_eq!(degenerate.stats().one_child, 6);
+    }
+}
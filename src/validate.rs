@@ -0,0 +1,313 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains a checker of structural invariants of BSTMap, meant for tests and debug assertions
+//
+// The tree is plain (no colors, heights or sizes stored in nodes), so the invariants are:
+// - every key is between the keys of its ancestors, according to the comparator
+// - length matches the number of nodes
+
+use std::{error::Error, fmt};
+
+use crate::{BSTMap, Comparator, Node};
+
+// Child taken on the way down from the root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    // Key is in the right subtree of the ancestor at given depth, but is not greater than its key
+    NotAfterAncestor { ancestor_depth: usize },
+    // Key is in the left subtree of the ancestor at given depth, but is not less than its key
+    NotBeforeAncestor { ancestor_depth: usize },
+    // Stored length differs from the number of nodes in the tree
+    LengthMismatch { length: usize, nodes: usize },
+}
+
+// First violation found, along with the path from the root to the offending node
+// (empty for the root or for violations concerning the whole tree)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub path: Vec<Direction>,
+    pub violation: Violation,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = |path: &[Direction]| {
+            path.iter().fold(String::from("root"), |acc, direction| {
+                acc + match direction {
+                    Direction::Left => "/L",
+                    Direction::Right => "/R",
+                }
+            })
+        };
+
+        match self.violation {
+            Violation::NotAfterAncestor { ancestor_depth } => write!(
+                f,
+                "key at {} is not greater than key at {}, although it is in its right subtree",
+                path(&self.path),
+                path(&self.path[..ancestor_depth])
+            ),
+            Violation::NotBeforeAncestor { ancestor_depth } => write!(
+                f,
+                "key at {} is not less than key at {}, although it is in its left subtree",
+                path(&self.path),
+                path(&self.path[..ancestor_depth])
+            ),
+            Violation::LengthMismatch { length, nodes } => {
+                write!(f, "length is {length}, but the tree has {nodes} nodes")
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+// Node waiting to be checked
+struct Visit<'a, K, V> {
+    node: &'a Node<K, V>,
+    // Index of the last step of the path to the node, None for the root
+    step: Option<usize>,
+    depth: usize,
+    // Closest ancestors bounding the key from below and above, along with their depths
+    lower: Option<(&'a K, usize)>,
+    upper: Option<(&'a K, usize)>,
+}
+
+impl<K, V, C: Comparator<K>> BSTMap<K, V, C> {
+    // Checks the invariants of the tree, e.g. `debug_assert_eq!(bst.validate(), Ok(()))`
+    // Takes O(n) time, the same as a full iteration
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        // Every step down the tree, as the index of the previous step and the direction taken.
+        // Paths are shared this way and rebuilt only once a violation is found
+        let mut steps: Vec<(Option<usize>, Direction)> = vec![];
+        let mut stack: Vec<Visit<K, V>> = self
+            .head
+            .as_deref()
+            .map(|node| Visit {
+                node,
+                step: None,
+                depth: 0,
+                lower: None,
+                upper: None,
+            })
+            .into_iter()
+            .collect();
+        let mut nodes = 0;
+
+        while let Some(visit) = stack.pop() {
+            let node = visit.node;
+            nodes += 1;
+
+            let violation = if let Some((key, depth)) = visit.lower
+                && self.comparator.compare(&node.key, key).is_le()
+            {
+                Some(Violation::NotAfterAncestor {
+                    ancestor_depth: depth,
+                })
+            } else if let Some((key, depth)) = visit.upper
+                && self.comparator.compare(&node.key, key).is_ge()
+            {
+                Some(Violation::NotBeforeAncestor {
+                    ancestor_depth: depth,
+                })
+            } else {
+                None
+            };
+
+            if let Some(violation) = violation {
+                let mut path = vec![];
+                let mut step = visit.step;
+                while let Some(i) = step {
+                    path.push(steps[i].1);
+                    step = steps[i].0;
+                }
+                path.reverse();
+
+                return Err(ValidationError { path, violation });
+            }
+
+            let bound = Some((&node.key, visit.depth));
+
+            if let Some(left) = node.left.as_deref() {
+                steps.push((visit.step, Direction::Left));
+                stack.push(Visit {
+                    node: left,
+                    step: Some(steps.len() - 1),
+                    depth: visit.depth + 1,
+                    lower: visit.lower,
+                    upper: bound,
+                });
+            }
+            if let Some(right) = node.right.as_deref() {
+                steps.push((visit.step, Direction::Right));
+                stack.push(Visit {
+                    node: right,
+                    step: Some(steps.len() - 1),
+                    depth: visit.depth + 1,
+                    lower: bound,
+                    upper: visit.upper,
+                });
+            }
+        }
+
+        if nodes != self.length {
+            return Err(ValidationError {
+                path: vec![],
+                violation: Violation::LengthMismatch {
+                    length: self.length,
+                    nodes,
+                },
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BSTMap, Node, ReverseComparator};
+
+    use super::{Direction, ValidationError, Violation};
+
+    fn sample() -> BSTMap<u32, ()> {
+        [50, 30, 70, 20, 40, 60, 80]
+            .into_iter()
+            .map(|k| (k, ()))
+            .collect()
+    }
+
+    #[test]
+    fn valid_tree_passes() {
+        let mut bst = sample();
+        assert_eq!(bst.validate(), Ok(()));
+
+        bst.remove(50);
+        bst.remove(20);
+        assert_eq!(bst.validate(), Ok(()));
+        assert_eq!(BSTMap::<u32, ()>::new().validate(), Ok(()));
+    }
+
+    #[test]
+    fn tree_stays_valid_after_every_kind_of_removal() {
+        // Trees from the removal tests in lib.rs
+        let one_child = [10, 5, 15, 2, 20];
+        let right_node_successor = [10, 5, 15, 2, 13, 20, 12, 14];
+        let successor_in_right_subtree = [10, 5, 15, 2, 13, 20, 12, 14, 19, 17, 21];
+
+        for (keys, removals) in [
+            (&one_child[..], &[5, 15][..]),
+            (&right_node_successor[..], &[15][..]),
+            (&successor_in_right_subtree[..], &[15, 10][..]),
+        ] {
+            let mut bst: BSTMap<u32, ()> = keys.iter().map(|k| (*k, ())).collect();
+
+            for key in removals {
+                assert!(bst.remove(*key).is_some());
+                assert_eq!(bst.validate(), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn validation_uses_comparator_of_the_map() {
+        let mut bst: BSTMap<u32, (), ReverseComparator> =
+            [50, 30, 70, 20, 40].into_iter().map(|k| (k, ())).collect();
+        assert_eq!(bst.validate(), Ok(()));
+
+        bst.remove(50);
+        assert_eq!(bst.validate(), Ok(()));
+
+        // Ascending order is out of order for this map
+        let ascending = BSTMap {
+            head: Some(Box::new(Node {
+                left: Some(Box::new(Node::new(1, ()))),
+                ..Node::new(2, ())
+            })),
+            length: 2,
+            comparator: ReverseComparator,
+        };
+        assert_eq!(
+            ascending.validate().unwrap_err().violation,
+            Violation::NotBeforeAncestor { ancestor_depth: 0 }
+        );
+    }
+
+    #[test]
+    fn key_out_of_order_is_reported_with_its_path() {
+        let mut bst = sample();
+
+        // 55 is greater than 30 and 40 as it should be, but it is in the left subtree of the root (50)
+        let node_40 = bst
+            .head
+            .as_mut()
+            .unwrap()
+            .left
+            .as_mut()
+            .unwrap()
+            .right
+            .as_mut();
+        node_40.unwrap().right = Some(Box::new(Node::new(55, ())));
+        bst.length += 1;
+
+        let error = bst.validate().unwrap_err();
+        assert_eq!(
+            error,
+            ValidationError {
+                path: vec![Direction::Left, Direction::Right, Direction::Right],
+                violation: Violation::NotBeforeAncestor { ancestor_depth: 0 },
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "key at root/L/R/R is not less than key at root, although it is in its left subtree"
+        );
+    }
+
+    #[test]
+    fn duplicate_key_and_wrong_length_are_reported() {
+        let mut bst = sample();
+
+        let node_70 = bst.head.as_mut().unwrap().right.as_mut().unwrap();
+        // duplicate of 70 below 60, bounded from above by 60 first
+        node_70.left.as_mut().unwrap().left = Some(Box::new(Node::new(70, ())));
+        assert_eq!(
+            bst.validate().unwrap_err().violation,
+            Violation::NotBeforeAncestor { ancestor_depth: 2 }
+        );
+
+        let node_70 = bst.head.as_mut().unwrap().right.as_mut().unwrap();
+        node_70.left.as_mut().unwrap().left = None;
+        bst.length += 1;
+        assert_eq!(
+            bst.validate(),
+            Err(ValidationError {
+                path: vec![],
+                violation: Violation::LengthMismatch {
+                    length: 8,
+                    nodes: 7
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,240 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains multi-version map with snapshot-isolated reads
+//
+// Every version of the map is a PersistentBSTMap, so taking a snapshot is an O(1) clone and versions
+// share all subtrees which were not modified between them. Writers build the next version on the side
+// and publish it with a quick swap, so readers holding a snapshot never block them and never see
+// a write in progress. Old versions are reference counted - a version (or its nodes not shared with
+// newer versions) is freed as soon as the last snapshot referencing it is dropped
+
+use std::{
+    ops::RangeBounds,
+    sync::{Mutex, RwLock},
+};
+
+use crate::{PersistentBSTMap, PersistentBSTMapIter};
+
+// Consistent, read-only view of the map at a given version
+pub struct BSTMapSnapshot<K: Ord, V> {
+    version: u64,
+    map: PersistentBSTMap<K, V>,
+}
+
+impl<K: Ord, V> BSTMapSnapshot<K, V> {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.map.get(key)
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.map.contains(key)
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.map.first_key_value()
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.map.last_key_value()
+    }
+
+    pub fn iter(&self) -> PersistentBSTMapIter<'_, K, V> {
+        self.map.iter()
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> PersistentBSTMapIter<'_, K, V> {
+        self.map.range(range)
+    }
+}
+
+// O(1), the clone refers to the same version
+impl<K: Ord, V> Clone for BSTMapSnapshot<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            version: self.version,
+            map: self.map.clone(),
+        }
+    }
+}
+
+pub struct VersionedBSTMap<K: Ord, V> {
+    // latest published version, only locked for the duration of a clone or a swap
+    current: RwLock<BSTMapSnapshot<K, V>>,
+    // serializes writers, so that no write is lost between building and publishing a version
+    writer: Mutex<()>,
+}
+
+impl<K: Ord, V> VersionedBSTMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            current: RwLock::new(BSTMapSnapshot {
+                version: 0,
+                map: PersistentBSTMap::new(),
+            }),
+            writer: Mutex::new(()),
+        }
+    }
+
+    // Version of the latest write, 0 if nothing was written yet
+    pub fn version(&self) -> u64 {
+        self.current.read().unwrap().version
+    }
+
+    pub fn len(&self) -> usize {
+        self.current.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Point-in-time view of the map, unaffected by any later writes
+    pub fn snapshot(&self) -> BSTMapSnapshot<K, V> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> VersionedBSTMap<K, V> {
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.current.read().unwrap().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.write(|map| map.insert(key, value))
+    }
+
+    pub fn remove(&self, key: K) -> Option<V> {
+        self.write(|map| map.remove(key))
+    }
+
+    pub fn clear(&self) {
+        self.write(|map| map.clear())
+    }
+
+    // Applies all changes made by f as a single new version
+    // Snapshots see either none or all of them
+    pub fn write<R>(&self, f: impl FnOnce(&mut PersistentBSTMap<K, V>) -> R) -> R {
+        let _writer = self.writer.lock().unwrap();
+
+        let mut next = self.snapshot();
+        let result = f(&mut next.map);
+        next.version += 1;
+
+        // Previous version is dropped outside of the lock, freeing it may take a while
+        let _previous = std::mem::replace(&mut *self.current.write().unwrap(), next);
+
+        result
+    }
+}
+
+impl<K: Ord, V> Default for VersionedBSTMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::VersionedBSTMap;
+
+    #[test]
+    fn snapshot_does_not_see_later_writes() {
+        let bst = VersionedBSTMap::new();
+
+        bst.insert(1, "one");
+        bst.insert(2, "two");
+
+        let snapshot = bst.snapshot();
+
+        bst.insert(3, "three");
+        bst.remove(1);
+        bst.insert(2, "TWO");
+
+        assert_eq!(snapshot.version(), 2);
+        assert_eq!(bst.version(), 5);
+        assert_eq!(
+            snapshot.iter().collect::<Vec<_>>(),
+            vec![(&1, &"one"), (&2, &"two")]
+        );
+        assert_eq!(
+            bst.snapshot().iter().collect::<Vec<_>>(),
+            vec![(&2, &"TWO"), (&3, &"three")]
+        );
+        assert_eq!(bst.get(&2), Some("TWO"));
+    }
+
+    #[test]
+    fn batch_is_a_single_version() {
+        let bst = VersionedBSTMap::new();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for round in 0..100 {
+                    bst.write(|map| {
+                        for key in 0..10 {
+                            map.insert(key, round);
+                        }
+                    });
+                }
+            });
+
+            scope.spawn(|| {
+                for _ in 0..100 {
+                    let snapshot = bst.snapshot();
+                    let mut values = snapshot.iter().map(|(_, v)| *v);
+
+                    if let Some(first) = values.next() {
+                        assert!(values.all(|v| v == first));
+                        assert_eq!(snapshot.len(), 10);
+                    }
+                }
+            });
+        });
+
+        assert_eq!(bst.version(), 100);
+    }
+
+    #[test]
+    fn old_versions_are_freed_with_their_last_snapshot() {
+        let bst = VersionedBSTMap::new();
+        let value = Arc::new(());
+
+        bst.insert(1, value.clone());
+        let snapshot = bst.snapshot();
+        bst.remove(1);
+
+        // Only the snapshot still references the removed value
+        assert_eq!(Arc::strong_count(&value), 2);
+
+        drop(snapshot);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
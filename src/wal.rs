@@ -0,0 +1,539 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// This file contains the write-ahead log used by DurableBSTMap
+//
+// Layout of the log file:
+// - 8 bytes of magic (WAL_MAGIC)
+// - any number of records, each record being:
+//   - u32 length of the payload
+//   - u32 CRC-32 of the payload
+//   - payload - u8 opcode followed by length-prefixed key and value (if the op has them)
+//     batch payload is u8 opcode, u8 cleared flag, u64 number of writes and that many
+//     insert/remove payloads - a whole batch is a single record, so it is never replayed partially
+//
+// A last record which is cut short or has a bad checksum can only be the result of a crash
+// in the middle of a write, so it gets truncated on open. A bad checksum anywhere else
+// is corruption and opening fails, leaving the file as it is. The same goes for a record
+// running past the end of the file with complete records after its header - it is not
+// a torn write, but a damaged length
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{
+    BSTMap, Comparator, OrdComparator, WriteBatch,
+    batch::median_first,
+    codec::{ByteReader, Decode, Encode, crc32, invalid_data, put_framed, put_u32, put_u64},
+};
+
+const WAL_MAGIC: &[u8; 8] = b"RBSTWAL1";
+const RECORD_HEADER_LEN: usize = 8;
+
+const OP_INSERT: u8 = 1;
+const OP_REMOVE: u8 = 2;
+const OP_CLEAR: u8 = 3;
+const OP_BATCH: u8 = 4;
+
+// When should the log be flushed to the disk with fsync
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    // After every single operation - slow, but nothing acknowledged is ever lost
+    Always,
+    // After every N operations - up to N-1 most recent operations may be lost on power failure
+    EveryN(usize),
+    // Never, leave it up to the OS
+    Never,
+}
+
+// Single mutation, borrowing its arguments so they can be logged before being moved into the map
+pub(crate) enum WalOp<'a, K, V, C = OrdComparator> {
+    Insert(&'a K, &'a V),
+    Remove(&'a K),
+    Clear,
+    Batch(&'a WriteBatch<K, V, C>),
+}
+
+impl<K: Encode, V: Encode, C> WalOp<'_, K, V, C> {
+    fn encode_payload(&self, out: &mut Vec<u8>) {
+        match self {
+            WalOp::Insert(key, value) => {
+                out.push(OP_INSERT);
+                put_framed(out, *key);
+                put_framed(out, *value);
+            }
+            WalOp::Remove(key) => {
+                out.push(OP_REMOVE);
+                put_framed(out, *key);
+            }
+            WalOp::Clear => out.push(OP_CLEAR),
+            WalOp::Batch(batch) => {
+                out.push(OP_BATCH);
+                out.push(batch.cleared as u8);
+                put_u64(out, batch.len() as u64);
+
+                for (key, write) in batch.iter() {
+                    match write {
+                        Some(value) => WalOp::<K, V, C>::Insert(key, value).encode_payload(out),
+                        None => WalOp::<K, V, C>::Remove(key).encode_payload(out),
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Decodes a single payload (with valid checksum) and applies it to the map
+fn apply_payload<K: Decode, V: Decode, C: Comparator<K>>(
+    payload: &[u8],
+    bst: &mut BSTMap<K, V, C>,
+) -> io::Result<()> {
+    let mut reader = ByteReader::new(payload);
+
+    match reader.read_u8()? {
+        OP_INSERT => {
+            let key = K::decode(reader.read_framed()?)?;
+            let value = V::decode(reader.read_framed()?)?;
+            bst.insert(key, value);
+        }
+        OP_REMOVE => {
+            let key = K::decode(reader.read_framed()?)?;
+            bst.remove(key);
+        }
+        OP_CLEAR => bst.clear(),
+        OP_BATCH => {
+            // Decode the whole batch first, so that a malformed one does not get applied partially
+            let cleared = reader.read_u8()? != 0;
+            let mut writes = Vec::new();
+
+            for _ in 0..reader.read_u64()? {
+                match reader.read_u8()? {
+                    OP_INSERT => {
+                        let key = K::decode(reader.read_framed()?)?;
+                        let value = V::decode(reader.read_framed()?)?;
+                        writes.push((key, Some(value)));
+                    }
+                    OP_REMOVE => writes.push((K::decode(reader.read_framed()?)?, None)),
+                    _ => return Err(invalid_data("unknown opcode in write-ahead log batch")),
+                }
+            }
+
+            if cleared {
+                bst.clear();
+            }
+
+            // Writes are encoded in key order, which would make the tree a chain
+            for (key, write) in median_first(writes) {
+                match write {
+                    Some(value) => bst.insert(key, value),
+                    None => bst.remove(key),
+                };
+            }
+        }
+        _ => return Err(invalid_data("unknown opcode in write-ahead log")),
+    };
+
+    if !reader.is_empty() {
+        return Err(invalid_data("trailing bytes in write-ahead log record"));
+    }
+
+    Ok(())
+}
+
+// Returns the payload of the next record, or None if the record is torn
+// Only the last record can be torn - a bad checksum with more records after it is corruption
+// (truncating there would throw away records which were already committed)
+fn next_payload<'a>(reader: &mut ByteReader<'a>) -> io::Result<Option<&'a [u8]>> {
+    let Ok(payload_len) = reader.read_u32() else {
+        return Ok(None);
+    };
+    let Ok(checksum) = reader.read_u32() else {
+        return Ok(None);
+    };
+    let Ok(payload) = reader.read_bytes(payload_len as usize) else {
+        // A torn write leaves a prefix of a single record behind, there is no other record in it
+        if holds_record(reader.remaining()) {
+            return Err(invalid_data(
+                "write-ahead log record runs past the end of the log and is followed by other records",
+            ));
+        }
+
+        return Ok(None);
+    };
+
+    if payload.is_empty() || crc32(payload) != checksum {
+        if reader.is_empty() {
+            return Ok(None);
+        }
+
+        return Err(invalid_data(
+            "write-ahead log record has a bad checksum and is followed by other records",
+        ));
+    }
+
+    Ok(Some(payload))
+}
+
+// Whether a complete record with a valid checksum starts anywhere in bytes
+// Quadratic in the worst case, but it only ever runs over the tail of a log which failed to parse
+fn holds_record(bytes: &[u8]) -> bool {
+    (0..bytes.len()).any(|start| {
+        let mut reader = ByteReader::new(&bytes[start..]);
+
+        let (Ok(payload_len), Ok(checksum)) = (reader.read_u32(), reader.read_u32()) else {
+            return false;
+        };
+
+        reader
+            .read_bytes(payload_len as usize)
+            .is_ok_and(|payload| !payload.is_empty() && crc32(payload) == checksum)
+    })
+}
+
+// Replays records of a log (contents after the magic) into bst
+// Returns the length of the valid part, which is shorter than contents if the last record is torn
+fn replay_records<K: Decode, V: Decode, C: Comparator<K>>(
+    contents: &[u8],
+    bst: &mut BSTMap<K, V, C>,
+) -> io::Result<usize> {
+    let mut reader = ByteReader::new(contents);
+    let mut valid_len = 0;
+
+    while let Some(payload) = next_payload(&mut reader)? {
+        apply_payload(payload, bst)?;
+        valid_len = reader.position();
+    }
+
+    Ok(valid_len)
+}
+
+// Replays a log segment which is no longer written to
+// It has been fully synced before the next one was started, so a torn record means corruption
+pub(crate) fn replay_sealed<K: Decode, V: Decode, C: Comparator<K>>(
+    path: &Path,
+    bst: &mut BSTMap<K, V, C>,
+) -> io::Result<()> {
+    let contents = std::fs::read(path)?;
+
+    if replay_log(&contents, bst)? != contents.len() {
+        return Err(invalid_data("sealed write-ahead log segment is corrupted"));
+    }
+
+    Ok(())
+}
+
+// Replays the contents of a log file (magic included) into bst
+// Returns the length of the valid part, which is shorter than contents if the last record is torn
+pub(crate) fn replay_log<K: Decode, V: Decode, C: Comparator<K>>(
+    contents: &[u8],
+    bst: &mut BSTMap<K, V, C>,
+) -> io::Result<usize> {
+    if !contents.starts_with(WAL_MAGIC) {
+        return Err(invalid_data("file is not a write-ahead log"));
+    }
+
+    Ok(WAL_MAGIC.len() + replay_records(&contents[WAL_MAGIC.len()..], bst)?)
+}
+
+// Overwrites checksums of all complete records with the correct ones,
+// so that fuzzing gets past them and into the payloads
+#[cfg(feature = "fuzzing")]
+pub(crate) fn fix_checksums(contents: &mut [u8]) {
+    let mut position = WAL_MAGIC.len();
+
+    while let Some(header) = contents.get(position..position + RECORD_HEADER_LEN) {
+        // unwrap is safe, header is RECORD_HEADER_LEN bytes long
+        let payload_len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let payload_start = position + RECORD_HEADER_LEN;
+
+        let Some(payload) = payload_start
+            .checked_add(payload_len)
+            .and_then(|payload_end| contents.get(payload_start..payload_end))
+        else {
+            break;
+        };
+
+        let checksum = crc32(payload);
+        contents[position + 4..payload_start].copy_from_slice(&checksum.to_le_bytes());
+        position = payload_start + payload_len;
+    }
+}
+
+pub(crate) struct WalWriter {
+    file: File,
+    policy: FsyncPolicy,
+    unsynced: usize,
+    len: u64,
+    buffer: Vec<u8>,
+}
+
+impl WalWriter {
+    // Opens (or creates) the log at path, replaying all of its records into bst
+    pub(crate) fn open<K: Decode, V: Decode, C: Comparator<K>>(
+        path: &Path,
+        policy: FsyncPolicy,
+        bst: &mut BSTMap<K, V, C>,
+    ) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        let valid_len = if contents.len() < WAL_MAGIC.len() {
+            // Empty file, or crashed while writing the magic - start from scratch
+            if !WAL_MAGIC.starts_with(&contents) {
+                return Err(invalid_data("file is not a write-ahead log"));
+            }
+
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(WAL_MAGIC)?;
+            WAL_MAGIC.len()
+        } else {
+            if &contents[..WAL_MAGIC.len()] != WAL_MAGIC {
+                return Err(invalid_data("file is not a write-ahead log"));
+            }
+
+            WAL_MAGIC.len() + replay_records(&contents[WAL_MAGIC.len()..], bst)?
+        };
+
+        // Get rid of the torn record, if there was one
+        if valid_len < contents.len() {
+            file.set_len(valid_len as u64)?;
+        }
+
+        file.seek(SeekFrom::Start(valid_len as u64))?;
+        file.sync_all()?;
+
+        Ok(Self {
+            file,
+            policy,
+            unsynced: 0,
+            len: valid_len as u64,
+            buffer: Vec::new(),
+        })
+    }
+
+    // Size of the log file in bytes
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub(crate) fn append<K: Encode, V: Encode, C>(
+        &mut self,
+        op: &WalOp<K, V, C>,
+    ) -> io::Result<()> {
+        self.buffer.clear();
+        put_u32(&mut self.buffer, 0);
+        put_u32(&mut self.buffer, 0);
+        op.encode_payload(&mut self.buffer);
+
+        let payload = &self.buffer[RECORD_HEADER_LEN..];
+        let payload_len = payload.len() as u32;
+        let checksum = crc32(payload);
+        self.buffer[0..4].copy_from_slice(&payload_len.to_le_bytes());
+        self.buffer[4..8].copy_from_slice(&checksum.to_le_bytes());
+
+        // Single write per record, so that a crash leaves at most one torn record at the end
+        self.file.write_all(&self.buffer)?;
+        self.len += self.buffer.len() as u64;
+        self.unsynced += 1;
+
+        match self.policy {
+            FsyncPolicy::Always => self.sync(),
+            FsyncPolicy::EveryN(n) if self.unsynced >= n.max(1) => self.sync(),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, OpenOptions},
+        io::Write,
+    };
+
+    use super::{FsyncPolicy, WAL_MAGIC, WalOp, WalWriter, replay_sealed};
+    use crate::{BSTMap, durable::tests::temp_path};
+
+    #[test]
+    fn torn_record_is_truncated_and_earlier_records_survive() {
+        let path = temp_path("wal_torn");
+
+        {
+            let mut bst = BSTMap::<u32, String>::new();
+            let mut wal = WalWriter::open(&path, FsyncPolicy::Never, &mut bst).unwrap();
+            wal.append(&WalOp::<_, _>::Insert(&1u32, &"one".to_string()))
+                .unwrap();
+            wal.append(&WalOp::<_, _>::Insert(&2u32, &"two".to_string()))
+                .unwrap();
+        }
+
+        // Chop off the last few bytes of the second record, as if the write was interrupted
+        let full_len = fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(full_len - 3).unwrap();
+        drop(file);
+
+        let mut bst = BSTMap::<u32, String>::new();
+        WalWriter::open(&path, FsyncPolicy::Never, &mut bst).unwrap();
+
+        assert_eq!(bst.len(), 1);
+        assert_eq!(bst.get(&1).unwrap(), "one");
+        assert!(bst.get(&2).is_none());
+        assert!(fs::metadata(&path).unwrap().len() < full_len - 3);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn torn_record_in_sealed_segment_is_an_error() {
+        let path = temp_path("wal_sealed_torn");
+
+        {
+            let mut bst = BSTMap::<u32, u32>::new();
+            let mut wal = WalWriter::open(&path, FsyncPolicy::Never, &mut bst).unwrap();
+            wal.append(&WalOp::<_, _>::Insert(&1u32, &10u32)).unwrap();
+        }
+
+        let mut bst = BSTMap::<u32, u32>::new();
+        replay_sealed(&path, &mut bst).unwrap();
+        assert_eq!(bst.len(), 1);
+
+        let full_len = fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(full_len - 1).unwrap();
+        drop(file);
+
+        let mut bst = BSTMap::<u32, u32>::new();
+        assert!(replay_sealed(&path, &mut bst).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn record_with_bad_checksum_is_truncated() {
+        let path = temp_path("wal_checksum");
+
+        {
+            let mut bst = BSTMap::<u32, u32>::new();
+            let mut wal = WalWriter::open(&path, FsyncPolicy::Never, &mut bst).unwrap();
+            wal.append(&WalOp::<_, _>::Insert(&1u32, &10u32)).unwrap();
+            wal.append(&WalOp::<_, _>::Insert(&2u32, &20u32)).unwrap();
+        }
+
+        // Flip the last byte of the file, which belongs to the value of the second record
+        let mut contents = fs::read(&path).unwrap();
+        let last = contents.len() - 1;
+        contents[last] ^= 0xff;
+        fs::write(&path, &contents).unwrap();
+
+        let mut bst = BSTMap::<u32, u32>::new();
+        WalWriter::open(&path, FsyncPolicy::Never, &mut bst).unwrap();
+
+        assert_eq!(bst.len(), 1);
+        assert_eq!(*bst.get(&1).unwrap(), 10);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bad_checksum_before_other_records_is_an_error_and_nothing_is_truncated() {
+        let path = temp_path("wal_checksum_middle");
+
+        {
+            let mut bst = BSTMap::<u32, u32>::new();
+            let mut wal = WalWriter::open(&path, FsyncPolicy::Never, &mut bst).unwrap();
+            wal.append(&WalOp::<_, _>::Insert(&1u32, &10u32)).unwrap();
+            wal.append(&WalOp::<_, _>::Insert(&2u32, &20u32)).unwrap();
+        }
+
+        // Flip the first payload byte (opcode of the first record)
+        let mut contents = fs::read(&path).unwrap();
+        contents[WAL_MAGIC.len() + 8] ^= 0xff;
+        fs::write(&path, &contents).unwrap();
+
+        let mut bst = BSTMap::<u32, u32>::new();
+        assert!(WalWriter::open(&path, FsyncPolicy::Never, &mut bst).is_err());
+        assert_eq!(fs::read(&path).unwrap(), contents);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn damaged_length_before_other_records_is_an_error_and_nothing_is_truncated() {
+        let path = temp_path("wal_length_middle");
+
+        {
+            let mut bst = BSTMap::<u32, u32>::new();
+            let mut wal = WalWriter::open(&path, FsyncPolicy::Never, &mut bst).unwrap();
+            for key in 0..3u32 {
+                wal.append(&WalOp::<_, _>::Insert(&key, &(key * 10)))
+                    .unwrap();
+            }
+        }
+
+        // Set the high bit of the first record's length, so it runs past the end of the file
+        let mut contents = fs::read(&path).unwrap();
+        contents[WAL_MAGIC.len() + 3] |= 0x80;
+        fs::write(&path, &contents).unwrap();
+
+        let mut bst = BSTMap::<u32, u32>::new();
+        assert!(WalWriter::open(&path, FsyncPolicy::Never, &mut bst).is_err());
+        assert_eq!(fs::metadata(&path).unwrap().len(), contents.len() as u64);
+        assert_eq!(fs::read(&path).unwrap(), contents);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_with_foreign_contents_is_rejected() {
+        let path = temp_path("wal_foreign");
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"definitely not a log").unwrap();
+        drop(file);
+
+        let mut bst = BSTMap::<u32, u32>::new();
+        assert!(WalWriter::open(&path, FsyncPolicy::Never, &mut bst).is_err());
+
+        // Header cut short is fine though, it is a crash during creation
+        fs::write(&path, &WAL_MAGIC[..3]).unwrap();
+        assert!(WalWriter::open(&path, FsyncPolicy::Never, &mut bst).is_ok());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+// rbst328 - Implementation of Binary Search Tree in Rust
+// Copyright (C) 2025  Maciej Sawka <maciejsawka@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Model-based tests - random sequences of operations are applied to both BSTMap and std's BTreeMap,
+// every operation has to return the same result and leave BSTMap valid
+//
+// proptest shrinks failing sequences, so a failure is reported with a (close to) minimal list of operations.
+// Keys come from a small range, so that sequences often hit existing keys
+
+use std::{
+    collections::BTreeMap,
+    ops::{Bound, RangeBounds},
+};
+
+use proptest::prelude::*;
+use rbst328::BSTMap;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(u8, u32),
+    Remove(u8),
+    Get(u8),
+    GetMut(u8, u32),
+    Contains(u8),
+    Range(Bound<u8>, Bound<u8>),
+    PopFirst,
+    PopLast,
+    FirstLast,
+    Iter,
+    IterMut(u32),
+    Clear,
+}
+
+fn key() -> impl Strategy<Value = u8> {
+    0..64u8
+}
+
+fn bound() -> impl Strategy<Value = Bound<u8>> {
+    prop_oneof![
+        key().prop_map(Bound::Included),
+        key().prop_map(Bound::Excluded),
+        Just(Bound::Unbounded),
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        // mostly inserts and removals, so that trees grow and shrink in interesting ways
+        16 => (key(), any::<u32>()).prop_map(|(k, v)| Op::Insert(k, v)),
+        8 => key().prop_map(Op::Remove),
+        4 => key().prop_map(Op::Get),
+        2 => (key(), any::<u32>()).prop_map(|(k, v)| Op::GetMut(k, v)),
+        2 => key().prop_map(Op::Contains),
+        4 => (bound(), bound()).prop_map(|(start, end)| Op::Range(start, end)),
+        2 => Just(Op::PopFirst),
+        2 => Just(Op::PopLast),
+        2 => Just(Op::FirstLast),
+        2 => Just(Op::Iter),
+        2 => any::<u32>().prop_map(Op::IterMut),
+        // rare, otherwise trees would hardly ever get big
+        1 => Just(Op::Clear),
+    ]
+}
+
+fn check(ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut bst = BSTMap::new();
+    let mut model = BTreeMap::new();
+
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => prop_assert_eq!(bst.insert(k, v), model.insert(k, v)),
+            Op::Remove(k) => prop_assert_eq!(bst.remove(k), model.remove(&k)),
+            Op::Get(k) => prop_assert_eq!(bst.get(&k), model.get(&k)),
+            Op::GetMut(k, v) => {
+                let (bst_value, model_value) = (bst.get_mut(&k), model.get_mut(&k));
+                prop_assert_eq!(&bst_value, &model_value);
+
+                if let (Some(bst_value), Some(model_value)) = (bst_value, model_value) {
+                    *bst_value = v;
+                    *model_value = v;
+                }
+            }
+            Op::Contains(k) => prop_assert_eq!(bst.contains(k), model.contains_key(&k)),
+            Op::Range(start, end) => {
+                // BTreeMap::range panics on empty ranges with start after end, filtering does not
+                let expected: Vec<_> = model
+                    .iter()
+                    .filter(|(k, _)| (start, end).contains(*k))
+                    .collect();
+                prop_assert_eq!(bst.range((start, end)).collect::<Vec<_>>(), expected);
+            }
+            Op::PopFirst => prop_assert_eq!(bst.pop_first(), model.pop_first()),
+            Op::PopLast => prop_assert_eq!(bst.pop_last(), model.pop_last()),
+            Op::FirstLast => {
+                prop_assert_eq!(bst.first_key_value(), model.first_key_value());
+                prop_assert_eq!(bst.last_key_value(), model.last_key_value());
+            }
+            Op::Iter => {
+                prop_assert!(bst.iter().eq(model.iter()));
+                prop_assert!(bst.keys().eq(model.keys()));
+                prop_assert!(bst.values().eq(model.values()));
+            }
+            Op::IterMut(add) => {
+                for (_, value) in bst.iter_mut() {
+                    *value = value.wrapping_add(add);
+                }
+                for (_, value) in model.iter_mut() {
+                    *value = value.wrapping_add(add);
+                }
+            }
+            Op::Clear => {
+                bst.clear();
+                model.clear();
+            }
+        }
+
+        prop_assert_eq!(bst.len(), model.len());
+        if let Err(error) = bst.validate() {
+            return Err(TestCaseError::fail(error.to_string()));
+        }
+    }
+
+    prop_assert!(bst.into_iter().eq(model));
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn bstmap_behaves_like_btreemap(ops in prop::collection::vec(op(), 0..300)) {
+        check(ops)?;
+    }
+}